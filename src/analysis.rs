@@ -0,0 +1,650 @@
+//! Static analysis of independent styles.
+//!
+//! Styles reference their macros by name, so the structure of a style is only
+//! apparent after resolving these references. A [`MacroGraph`] records which
+//! macros call each other and which are used at all.
+//! [`IndependentStyle::inline_macros`] replaces all macro calls in the layouts
+//! with the macros' contents. [`IndependentStyle::usage`] determines which
+//! variables, terms, and item types a style references.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::taxonomy::{
+    DateVariable, Kind, NameVariable, NumberOrPageVariable, NumberVariable, OtherTerm,
+    StandardVariable, Term, Variable,
+};
+use crate::visit::{
+    Visit, VisitMut, walk_bibliography, walk_choose_branch, walk_citation,
+    walk_element_mut, walk_names,
+};
+use crate::{
+    Bibliography, ChooseBranch, Citation, CslMacro, Date, EtAl, Group, IndependentStyle,
+    Label, LayoutRenderingElement, Name, NameAnd, Names, Number, SortKey, Text,
+    TextTarget,
+};
+
+/// The calls between the macros of a style.
+///
+/// Like during rendering, only the first definition of each macro name is
+/// considered.
+#[derive(Debug, Clone)]
+pub struct MacroGraph<'a> {
+    /// The names of all defined macros in definition order, without
+    /// duplicates.
+    macros: Vec<&'a str>,
+    /// The macros called by each macro.
+    calls: HashMap<&'a str, Vec<&'a str>>,
+    /// The macros called directly from the layouts.
+    layout_calls: Vec<&'a str>,
+    /// The macros called from sort keys.
+    sort_calls: Vec<&'a str>,
+}
+
+impl IndependentStyle {
+    /// Build the call graph of the style's macros.
+    pub fn macro_graph(&self) -> MacroGraph<'_> {
+        MacroGraph::new(self)
+    }
+}
+
+impl<'a> MacroGraph<'a> {
+    fn new(style: &'a IndependentStyle) -> Self {
+        let mut macros = Vec::new();
+        let mut calls = HashMap::new();
+        for m in &style.macros {
+            calls.entry(m.name.as_str()).or_insert_with(|| {
+                macros.push(m.name.as_str());
+                let mut called = MacroCalls::default();
+                called.visit_macro(m);
+                called.layout
+            });
+        }
+
+        let mut called = MacroCalls::default();
+        called.visit_citation(&style.citation);
+        if let Some(bibliography) = &style.bibliography {
+            called.visit_bibliography(bibliography);
+        }
+
+        Self {
+            macros,
+            calls,
+            layout_calls: called.layout,
+            sort_calls: called.sort,
+        }
+    }
+
+    /// Whether a macro with this name is defined.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.calls.contains_key(name)
+    }
+
+    /// The macros directly called by a macro, in document order and
+    /// without duplicates.
+    pub fn callees(&self, name: &str) -> Vec<&'a str> {
+        let mut callees: Vec<&'a str> = Vec::new();
+        for &callee in self.calls.get(name).into_iter().flatten() {
+            if !callees.contains(&callee) {
+                callees.push(callee);
+            }
+        }
+        callees
+    }
+
+    /// The macros that directly call a macro, in definition order.
+    pub fn callers(&self, name: &str) -> Vec<&'a str> {
+        self.macros
+            .iter()
+            .copied()
+            .filter(|m| self.calls[m].contains(&name))
+            .collect()
+    }
+
+    /// The macros that are referenced neither by the layouts, nor by sort
+    /// keys, nor by other macros.
+    pub fn unused(&self) -> Vec<&'a str> {
+        let referenced: HashSet<&str> = self
+            .calls
+            .values()
+            .flatten()
+            .chain(&self.layout_calls)
+            .chain(&self.sort_calls)
+            .copied()
+            .collect();
+        self.macros
+            .iter()
+            .copied()
+            .filter(|m| !referenced.contains(m))
+            .collect()
+    }
+
+    /// The macros that can never be rendered because no chain of calls from
+    /// a layout or sort key leads to them. This includes the unused macros.
+    pub fn unreachable(&self) -> Vec<&'a str> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<&str> =
+            self.layout_calls.iter().chain(&self.sort_calls).copied().collect();
+        while let Some(name) = stack.pop() {
+            if reachable.insert(name) {
+                stack.extend(self.calls.get(name).into_iter().flatten());
+            }
+        }
+
+        self.macros
+            .iter()
+            .copied()
+            .filter(|m| !reachable.contains(m))
+            .collect()
+    }
+
+    /// The macros that are called but not defined, in order of their first
+    /// call.
+    pub fn undefined(&self) -> Vec<&'a str> {
+        let mut undefined: Vec<&'a str> = Vec::new();
+        let called = self
+            .layout_calls
+            .iter()
+            .chain(&self.sort_calls)
+            .chain(self.macros.iter().flat_map(|m| &self.calls[m]));
+        for &name in called {
+            if !self.is_defined(name) && !undefined.contains(&name) {
+                undefined.push(name);
+            }
+        }
+        undefined
+    }
+
+    /// Find the cycles of macro calls.
+    ///
+    /// Each cycle starts with its member that is defined first and is
+    /// reported only once, even if its members are part of other cycles.
+    pub fn cycles(&self) -> Vec<Vec<&'a str>> {
+        let mut cycles = Vec::new();
+        let mut reported = HashSet::new();
+        for &name in &self.macros {
+            let Some(cycle) = self.find_cycle(name) else { continue };
+            if cycle.iter().any(|name| reported.contains(name)) {
+                continue;
+            }
+            reported.extend(cycle.iter().copied());
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    /// Find a chain of calls that leads from `start` back to itself.
+    fn find_cycle(&self, start: &'a str) -> Option<Vec<&'a str>> {
+        fn visit<'a>(
+            current: &'a str,
+            start: &'a str,
+            calls: &HashMap<&'a str, Vec<&'a str>>,
+            visited: &mut HashSet<&'a str>,
+            chain: &mut Vec<&'a str>,
+        ) -> bool {
+            chain.push(current);
+            for &next in calls.get(current).into_iter().flatten() {
+                if next == start {
+                    return true;
+                }
+                if visited.insert(next) && visit(next, start, calls, visited, chain) {
+                    return true;
+                }
+            }
+            chain.pop();
+            false
+        }
+
+        let mut chain = Vec::new();
+        visit(start, start, &self.calls, &mut HashSet::new(), &mut chain).then_some(chain)
+    }
+}
+
+/// Collects the names of all macros directly called by the visited nodes.
+#[derive(Default)]
+struct MacroCalls<'a> {
+    layout: Vec<&'a str>,
+    sort: Vec<&'a str>,
+}
+
+impl<'a> Visit<'a> for MacroCalls<'a> {
+    fn visit_sort_key(&mut self, key: &'a SortKey) {
+        if let SortKey::MacroName { name, .. } = key {
+            self.sort.push(name);
+        }
+    }
+
+    fn visit_text(&mut self, text: &'a Text) {
+        if let TextTarget::Macro { name } = &text.target {
+            self.layout.push(name);
+        }
+    }
+}
+
+/// Why macros could not be inlined.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum InlineError {
+    /// A macro is called but not defined.
+    UndefinedMacro(String),
+    /// Macros call each other in a cycle. Contains the names of the
+    /// macros in the cycle.
+    RecursiveMacro(Vec<String>),
+    /// A macro call sets `quotes`, `strip-periods`, or `text-case`, which
+    /// cannot be expressed on a group.
+    UnsupportedCall(String),
+}
+
+impl fmt::Display for InlineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndefinedMacro(name) => write!(f, "macro `{name}` is not defined"),
+            Self::RecursiveMacro(cycle) => {
+                write!(f, "macros call each other recursively: {}", cycle.join(", "))
+            }
+            Self::UnsupportedCall(name) => write!(
+                f,
+                "call to macro `{name}` sets quotes, strip-periods, or text-case"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InlineError {}
+
+impl IndependentStyle {
+    /// Replace all macro calls in the layouts with the contents of the
+    /// called macros.
+    ///
+    /// Each call becomes a `cs:group` carrying the call's formatting, affixes,
+    /// and display attribute. Afterwards, only the macros that are referenced
+    /// by sort keys remain, with their calls inlined as well. The style is
+    /// left untouched if an error occurs.
+    pub fn inline_macros(&mut self) -> Result<(), InlineError> {
+        let graph = self.macro_graph();
+        if let Some(cycle) = graph.cycles().into_iter().next() {
+            return Err(InlineError::RecursiveMacro(
+                cycle.into_iter().map(ToString::to_string).collect(),
+            ));
+        }
+
+        let mut inliner = Inliner { macros: &self.macros, error: None };
+        let mut sort_macros: Vec<CslMacro> = Vec::new();
+        for m in &self.macros {
+            if graph.sort_calls.contains(&m.name.as_str())
+                && !sort_macros.iter().any(|s| s.name == m.name)
+            {
+                let mut m = m.clone();
+                inliner.visit_macro_mut(&mut m);
+                sort_macros.push(m);
+            }
+        }
+
+        let mut citation = self.citation.layout.clone();
+        inliner.visit_layout_mut(&mut citation);
+        let mut bibliography = self.bibliography.as_ref().map(|b| b.layout.clone());
+        if let Some(layout) = &mut bibliography {
+            inliner.visit_layout_mut(layout);
+        }
+
+        if let Some(error) = inliner.error {
+            return Err(error);
+        }
+
+        self.citation.layout = citation;
+        if let (Some(b), Some(layout)) = (&mut self.bibliography, bibliography) {
+            b.layout = layout;
+        }
+        self.macros = sort_macros;
+        Ok(())
+    }
+}
+
+/// Replaces macro calls with groups. The macros must not be recursive.
+struct Inliner<'a> {
+    macros: &'a [CslMacro],
+    error: Option<InlineError>,
+}
+
+impl Inliner<'_> {
+    fn expand(&mut self, text: &Text) -> Result<Group, InlineError> {
+        let TextTarget::Macro { name } = &text.target else { unreachable!() };
+        if text.quotes || text.strip_periods || text.text_case.is_some() {
+            return Err(InlineError::UnsupportedCall(name.clone()));
+        }
+
+        let Some(m) = self.macros.iter().find(|m| &m.name == name) else {
+            return Err(InlineError::UndefinedMacro(name.clone()));
+        };
+
+        let mut group = Group {
+            children: m.children.clone(),
+            font_style: text.formatting.font_style,
+            font_variant: text.formatting.font_variant,
+            font_weight: text.formatting.font_weight,
+            text_decoration: text.formatting.text_decoration,
+            vertical_align: text.formatting.vertical_align,
+            prefix: text.affixes.prefix.clone(),
+            suffix: text.affixes.suffix.clone(),
+            delimiter: None,
+            display: text.display,
+        };
+        self.visit_group_mut(&mut group);
+        Ok(group)
+    }
+}
+
+impl VisitMut for Inliner<'_> {
+    fn visit_element_mut(&mut self, element: &mut LayoutRenderingElement) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match element {
+            LayoutRenderingElement::Text(text)
+                if matches!(text.target, TextTarget::Macro { .. }) =>
+            {
+                match self.expand(text) {
+                    Ok(group) => *element = LayoutRenderingElement::Group(group),
+                    Err(error) => self.error = Some(error),
+                }
+            }
+            _ => walk_element_mut(self, element),
+        }
+    }
+}
+
+/// The variables, terms, and item types referenced by a style.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Usage {
+    /// The standard variables.
+    pub standard: HashSet<StandardVariable>,
+    /// The number variables, including the page variable.
+    pub number: HashSet<NumberOrPageVariable>,
+    /// The date variables.
+    pub date: HashSet<DateVariable>,
+    /// The name variables.
+    pub name: HashSet<NameVariable>,
+    /// The terms rendered by text, label, and et-al elements and for the
+    /// `and` attribute of names. Terms that are used implicitly, for example
+    /// for month names or ordinals, are not included.
+    pub terms: HashSet<Term>,
+    /// The item types tested by conditions.
+    pub kinds: HashSet<Kind>,
+}
+
+impl Usage {
+    /// All referenced variables.
+    pub fn variables(&self) -> impl Iterator<Item = Variable> + '_ {
+        self.standard
+            .iter()
+            .map(|&v| Variable::from(v))
+            .chain(self.number.iter().map(|&v| v.into()))
+            .chain(self.date.iter().map(|&v| v.into()))
+            .chain(self.name.iter().map(|&v| v.into()))
+    }
+
+    fn add_variable(&mut self, variable: Variable) {
+        match variable {
+            Variable::Standard(v) => {
+                self.standard.insert(v);
+            }
+            Variable::Page(v) => {
+                self.number.insert(NumberOrPageVariable::Page(v));
+            }
+            Variable::Number(v) => {
+                self.number.insert(NumberOrPageVariable::Number(v));
+            }
+            Variable::Date(v) => {
+                self.date.insert(v);
+            }
+            Variable::Name(v) => {
+                self.name.insert(v);
+            }
+        }
+    }
+
+    fn add_and(&mut self, and: Option<NameAnd>) {
+        if and == Some(NameAnd::Text) {
+            self.terms.insert(Term::Other(OtherTerm::And));
+        }
+    }
+}
+
+impl Visit<'_> for Usage {
+    fn visit_citation(&mut self, citation: &Citation) {
+        self.add_and(citation.name_options.and);
+        walk_citation(self, citation);
+    }
+
+    fn visit_bibliography(&mut self, bibliography: &Bibliography) {
+        self.add_and(bibliography.name_options.and);
+        walk_bibliography(self, bibliography);
+    }
+
+    fn visit_sort_key(&mut self, key: &SortKey) {
+        if let SortKey::Variable { variable, .. } = key {
+            self.add_variable(*variable);
+        }
+    }
+
+    fn visit_text(&mut self, text: &Text) {
+        match &text.target {
+            TextTarget::Variable { var, .. } => self.add_variable(*var),
+            TextTarget::Term { term, .. } => {
+                self.terms.insert(*term);
+            }
+            TextTarget::Macro { .. } | TextTarget::Value { .. } => {}
+        }
+    }
+
+    fn visit_date(&mut self, date: &Date) {
+        self.date.extend(date.variable);
+    }
+
+    fn visit_number(&mut self, number: &Number) {
+        self.number.insert(number.variable);
+    }
+
+    fn visit_label(&mut self, label: &Label) {
+        self.number.insert(label.variable);
+        self.terms.insert(label.variable.into());
+    }
+
+    fn visit_names(&mut self, names: &Names) {
+        self.name.extend(names.variable.iter().copied());
+        self.add_and(names.and);
+        if names.label().is_some() {
+            self.terms.extend(names.variable.iter().map(|&v| Term::from(v)));
+        }
+        walk_names(self, names);
+    }
+
+    fn visit_name(&mut self, name: &Name) {
+        self.add_and(name.options.and);
+    }
+
+    fn visit_et_al(&mut self, et_al: &EtAl) {
+        self.terms.insert(et_al.term.into());
+    }
+
+    fn visit_choose_branch(&mut self, branch: &ChooseBranch) {
+        let variables = branch.is_numeric.iter().chain(&branch.variable).flatten();
+        for &variable in variables {
+            self.add_variable(variable);
+        }
+        self.date.extend(branch.is_uncertain_date.iter().flatten().copied());
+        if branch.locator.is_some() {
+            self.number
+                .insert(NumberOrPageVariable::Number(NumberVariable::Locator));
+        }
+        self.kinds.extend(branch.type_.iter().flatten().copied());
+        walk_choose_branch(self, branch);
+    }
+}
+
+impl IndependentStyle {
+    /// Determine which variables, terms, and item types are referenced in the
+    /// citation, the bibliography, the macros, and the sort keys.
+    ///
+    /// All macros are included, even if they are never called.
+    pub fn usage(&self) -> Usage {
+        let mut usage = Usage::default();
+        usage.add_and(self.settings.options.and);
+        usage.visit_style(self);
+        usage
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FontStyle;
+    use crate::taxonomy::PageVariable;
+
+    const STYLE: &str = r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><id/><title/></info>
+  <macro name="author">
+    <names variable="author">
+      <substitute><text macro="editor"/></substitute>
+    </names>
+  </macro>
+  <macro name="editor">
+    <names variable="editor"/>
+  </macro>
+  <macro name="year">
+    <date variable="issued"><date-part name="year"/></date>
+  </macro>
+  <macro name="orphan">
+    <text macro="island"/>
+  </macro>
+  <macro name="island">
+    <text variable="title"/>
+  </macro>
+  <citation>
+    <sort><key macro="year"/></sort>
+    <layout>
+      <group delimiter=" ">
+        <text macro="author" font-style="italic" suffix=","/>
+        <choose>
+          <if variable="issued"><text macro="year"/></if>
+        </choose>
+      </group>
+    </layout>
+  </citation>
+</style>"#;
+
+    #[test]
+    fn macro_graph() {
+        let style = IndependentStyle::from_xml(STYLE).unwrap();
+        let graph = style.macro_graph();
+        assert_eq!(graph.callees("author"), ["editor"]);
+        assert_eq!(graph.callers("island"), ["orphan"]);
+        assert_eq!(graph.unused(), ["orphan"]);
+        assert_eq!(graph.unreachable(), ["orphan", "island"]);
+        assert!(graph.undefined().is_empty());
+        assert!(graph.cycles().is_empty());
+
+        let style = IndependentStyle::from_xml(
+            &STYLE
+                .replace(r#"<names variable="editor"/>"#, r#"<text macro="author"/>"#)
+                .replace(r#"<text variable="title"/>"#, r#"<text macro="missing"/>"#),
+        )
+        .unwrap();
+        let graph = style.macro_graph();
+        assert_eq!(graph.cycles(), [["author", "editor"]]);
+        assert_eq!(graph.undefined(), ["missing"]);
+    }
+
+    #[test]
+    fn inline_macros() {
+        let mut style = IndependentStyle::from_xml(STYLE).unwrap();
+        style.inline_macros().unwrap();
+        assert_eq!(style.macros.len(), 1);
+        assert_eq!(style.macros[0].name, "year");
+
+        assert!(style.macro_graph().layout_calls.is_empty());
+
+        let LayoutRenderingElement::Group(outer) = &style.citation.layout.elements[0]
+        else {
+            panic!("expected a group");
+        };
+        let LayoutRenderingElement::Group(author) = &outer.children[0] else {
+            panic!("expected the author macro to be inlined");
+        };
+        assert_eq!(author.font_style, Some(FontStyle::Italic));
+        assert_eq!(author.suffix.as_deref(), Some(","));
+        let LayoutRenderingElement::Names(names) = &author.children[0] else {
+            panic!("expected names");
+        };
+        assert!(matches!(
+            &names.substitute().unwrap().children[0],
+            LayoutRenderingElement::Group(g) if matches!(g.children[0], LayoutRenderingElement::Names(_))
+        ));
+
+        let mut style = IndependentStyle::from_xml(
+            &STYLE.replace(r#"font-style="italic""#, r#"text-case="uppercase""#),
+        )
+        .unwrap();
+        let original = style.clone();
+        assert_eq!(
+            style.inline_macros(),
+            Err(InlineError::UnsupportedCall("author".to_string()))
+        );
+        assert_eq!(style, original);
+    }
+
+    #[test]
+    fn usage() {
+        let style = IndependentStyle::from_xml(
+            r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><id/><title/></info>
+  <macro name="unused">
+    <text variable="URL"/>
+  </macro>
+  <citation>
+    <sort><key variable="citation-number"/></sort>
+    <layout>
+      <names variable="author" and="text">
+        <et-al term="and others"/>
+        <substitute><names variable="editor"/></substitute>
+      </names>
+      <choose>
+        <if type="book chapter" match="any"><text term="in"/></if>
+        <else-if is-numeric="volume" locator="page"><label variable="page"/></else-if>
+        <else><date variable="issued" form="numeric"/></else>
+      </choose>
+    </layout>
+  </citation>
+</style>"#,
+        )
+        .unwrap();
+
+        let usage = style.usage();
+        assert_eq!(usage.standard, HashSet::from([StandardVariable::URL]));
+        assert_eq!(
+            usage.number,
+            HashSet::from([
+                NumberOrPageVariable::Number(NumberVariable::CitationNumber),
+                NumberOrPageVariable::Number(NumberVariable::Volume),
+                NumberOrPageVariable::Number(NumberVariable::Locator),
+                NumberOrPageVariable::Page(PageVariable::Page),
+            ])
+        );
+        assert_eq!(usage.date, HashSet::from([DateVariable::Issued]));
+        assert_eq!(
+            usage.name,
+            HashSet::from([NameVariable::Author, NameVariable::Editor])
+        );
+        assert_eq!(
+            usage.terms,
+            HashSet::from([
+                Term::Other(OtherTerm::And),
+                Term::Other(OtherTerm::AndOthers),
+                Term::Other(OtherTerm::In),
+                Term::PageVariable,
+            ])
+        );
+        assert_eq!(usage.kinds, HashSet::from([Kind::Book, Kind::Chapter]));
+        assert_eq!(usage.variables().count(), 8);
+    }
+}
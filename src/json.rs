@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use unscanny::Scanner;
 
 use crate::taxonomy::Season;
+use crate::{FontStyle, FontVariant, FontWeight, Formatting, VerticalAlign};
 
 /// A CSL-JSON item.
 #[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
@@ -116,14 +117,36 @@ pub enum DateValue {
         raw: FixedDateRange,
         literal: Option<String>,
         season: Option<String>,
+        /// Whether the date is approximate.
+        circa: bool,
     },
     DateParts {
         date_parts: VecDateRange,
         literal: Option<String>,
         season: Option<String>,
+        /// Whether the date is approximate.
+        circa: bool,
     },
 }
 
+impl DateValue {
+    /// The literal text of the date, if any.
+    pub fn literal(&self) -> Option<&str> {
+        match self {
+            Self::Raw { literal, .. } | Self::DateParts { literal, .. } => {
+                literal.as_deref()
+            }
+        }
+    }
+
+    /// Whether the date is approximate.
+    pub fn circa(&self) -> bool {
+        match self {
+            Self::Raw { circa, .. } | Self::DateParts { circa, .. } => *circa,
+        }
+    }
+}
+
 impl TryFrom<DateValue> for FixedDateRange {
     type Error = ();
 
@@ -135,9 +158,12 @@ impl TryFrom<DateValue> for FixedDateRange {
                 (res, season)
             }
         };
-        fixed.start.season = season
+        if let Some(season) = season
             .and_then(|s| s.parse::<u8>().ok())
-            .and_then(|u| Season::try_from_csl_number(u).ok());
+            .and_then(|u| Season::try_from_csl_number(u).ok())
+        {
+            fixed.start.season = Some(season);
+        }
         Ok(fixed)
     }
 }
@@ -163,33 +189,59 @@ impl<'de> Deserialize<'de> for DateValue {
                 raw: FixedDateRange,
                 literal: Option<String>,
                 season: Option<NumberOrString>,
+                #[serde(default, deserialize_with = "deserialize_circa")]
+                circa: bool,
             },
             DateParts {
                 #[serde(rename = "date-parts")]
                 date_parts: VecDateRange,
                 literal: Option<String>,
                 season: Option<NumberOrString>,
+                #[serde(default, deserialize_with = "deserialize_circa")]
+                circa: bool,
             },
         }
 
         let raw = DateReprRaw::deserialize(deserializer)?;
         Ok(match raw {
-            DateReprRaw::Raw { raw, literal, season } => DateValue::Raw {
+            DateReprRaw::Raw { raw, literal, season, circa } => DateValue::Raw {
                 raw,
                 literal,
                 season: season.map(NumberOrString::into_string),
+                circa,
             },
-            DateReprRaw::DateParts { date_parts, literal, season } => {
+            DateReprRaw::DateParts { date_parts, literal, season, circa } => {
                 DateValue::DateParts {
                     date_parts,
                     literal,
                     season: season.map(NumberOrString::into_string),
+                    circa,
                 }
             }
         })
     }
 }
 
+/// Read the `circa` flag of a date. CSL-JSON allows booleans, numbers, and
+/// strings for it.
+fn deserialize_circa<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Circa {
+        Bool(bool),
+        Other(NumberOrString),
+    }
+
+    Ok(match Circa::deserialize(deserializer)? {
+        Circa::Bool(circa) => circa,
+        Circa::Other(other) => {
+            !matches!(other.into_string().as_str(), "" | "0" | "false")
+        }
+    })
+}
+
 impl Serialize for DateValue {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -271,8 +323,9 @@ impl TryFrom<VecDateRange> for FixedDateRange {
 
     fn try_from(value: VecDateRange) -> Result<Self, Self::Error> {
         let mut v = value.0.into_iter();
-        let start = v.next().ok_or(())?.into();
-        let end = v.next().map(|v| v.into());
+        let start = v.next().ok_or(())?.try_into()?;
+        // An end without a year leaves the range open.
+        let end = v.next().and_then(|v| v.try_into().ok());
         if v.next().is_some() {
             return Err(());
         }
@@ -313,13 +366,27 @@ pub struct FixedDate {
     pub season: Option<Season>,
 }
 
-impl From<VecDate> for FixedDate {
-    fn from(value: VecDate) -> Self {
+impl TryFrom<VecDate> for FixedDate {
+    type Error = ();
+
+    /// Convert the parts of a date. Fails if there is no year.
+    ///
+    /// Months from 21 to 24 are the seasons of CSL-JSON. Months and days out
+    /// of range are dropped, as is the day of a date without a month.
+    fn try_from(value: VecDate) -> Result<Self, Self::Error> {
         let mut v = value.0.into_iter();
-        let year = v.next().unwrap();
-        let month = v.next().map(|v| (v - 1) as u8);
-        let day = v.next().map(|v| (v - 1) as u8);
-        FixedDate { year, month, day, season: None }
+        let year = v.next().ok_or(())?;
+        let part = |v: Option<i16>, max: i16| {
+            v.filter(|v| (1..=max).contains(v)).map(|v| (v - 1) as u8)
+        };
+
+        let raw_month = v.next();
+        let season = raw_month
+            .and_then(|m| u8::try_from(m.checked_sub(20)?).ok())
+            .and_then(|n| Season::try_from_csl_number(n).ok());
+        let month = part(raw_month, 12);
+        let day = part(v.next(), 31).filter(|_| month.is_some());
+        Ok(FixedDate { year, month, day, season })
     }
 }
 
@@ -351,8 +418,11 @@ fn parse_date(s: &mut Scanner<'_>) -> Option<FixedDate> {
     s.eat();
 
     let month = s.eat_while(char::is_ascii_digit);
-    let month = month.parse::<u8>().ok()? - 1;
-    if month > 11 {
+    let month = month.parse::<u8>().ok()?.checked_sub(1)?;
+    if let 20..=23 = month {
+        let season = Season::try_from_csl_number(month - 19).ok();
+        return Some(FixedDate { year, month: None, day: None, season });
+    } else if month > 11 {
         return None;
     }
 
@@ -362,8 +432,8 @@ fn parse_date(s: &mut Scanner<'_>) -> Option<FixedDate> {
     s.eat();
 
     let day = s.eat_while(char::is_ascii_digit);
-    let day = day.parse::<u8>().ok()? - 1;
-    if day > 31 {
+    let day = day.parse::<u8>().ok()?.checked_sub(1)?;
+    if day > 30 {
         return None;
     }
 
@@ -380,6 +450,7 @@ fn parse_date(s: &mut Scanner<'_>) -> Option<FixedDate> {
 #[serde(rename_all = "camelCase")]
 pub struct Citation {
     /// A unique ID for the citation.
+    #[serde(alias = "citationID")]
     pub citation_id: String,
     /// The individual parts of the citation.
     pub citation_items: Vec<CitationItem>,
@@ -449,7 +520,285 @@ impl<'de> Deserialize<'de> for CitationItem {
 #[serde(rename_all = "camelCase")]
 pub struct CitationProperties {
     /// The footnote number in which the citation is located in the document.
-    note_index: Option<u32>,
+    pub note_index: Option<u32>,
+}
+
+/// Field content with the formatting markup that CSL-JSON allows: `<i>`,
+/// `<b>`, `<sup>`, `<sub>`, `<span style="font-variant:small-caps;">`, and
+/// `<span class="nocase">`.
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
+pub struct RichText(pub Vec<RichNode>);
+
+/// A piece of [`RichText`].
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum RichNode {
+    /// Text without markup.
+    Text(String),
+    /// Children with formatting applied.
+    Formatted(Formatting, Vec<RichNode>),
+    /// Children whose case must not be changed.
+    NoCase(Vec<RichNode>),
+}
+
+impl RichText {
+    /// Parse field content. Tags outside of the supported subset, closing
+    /// tags without an opening tag, and unclosed tags are kept as text.
+    ///
+    /// ```
+    /// use citationberg::json::RichText;
+    ///
+    /// let text = RichText::parse("On <i>Dune</i> &amp; <span class=\"nocase\">iOS</span>");
+    /// assert_eq!(text.to_string(), "On Dune & iOS");
+    /// assert_eq!(text.to_html(), "On <i>Dune</i> &amp; <span class=\"nocase\">iOS</span>");
+    /// ```
+    pub fn parse(text: &str) -> Self {
+        let mut s = Scanner::new(text);
+        let mut stack: Vec<OpenTag> = Vec::new();
+        let mut nodes = Vec::new();
+
+        while !s.done() {
+            let start = s.cursor();
+            if s.eat_if('<') {
+                let closing = s.eat_if('/');
+                let inner = s.eat_until(['<', '>']);
+                let tag = Tag::parse(inner);
+                if !s.eat_if('>') {
+                    // A literal `<`. Markup may follow it.
+                    s.jump(start + 1);
+                    push_rich_text(top(&mut stack, &mut nodes), "<");
+                } else if !closing && let Some(tag) = tag {
+                    stack.push(OpenTag {
+                        tag,
+                        source: s.from(start),
+                        children: Vec::new(),
+                    });
+                } else if closing
+                    && stack.iter().any(|open| open.tag.name() == inner.trim())
+                {
+                    // Close the tag and keep any unclosed tags inside it as text.
+                    while let Some(open) = stack.pop() {
+                        let parent = top(&mut stack, &mut nodes);
+                        if open.tag.name() == inner.trim() {
+                            parent.push(open.tag.wrap(open.children));
+                            break;
+                        }
+                        open.unwrap_into(parent);
+                    }
+                } else {
+                    push_rich_text(top(&mut stack, &mut nodes), s.from(start));
+                }
+            } else if s.eat_if('&') {
+                let entity = s.eat_while(|c: char| c.is_ascii_alphanumeric() || c == '#');
+                let current = top(&mut stack, &mut nodes);
+                match s.eat_if(';').then(|| decode_entity(entity)).flatten() {
+                    Some(c) => push_rich_text(current, c.encode_utf8(&mut [0; 4])),
+                    None => {
+                        s.jump(start + 1);
+                        push_rich_text(current, "&");
+                    }
+                }
+            } else {
+                let text = s.eat_until(['<', '&']);
+                push_rich_text(top(&mut stack, &mut nodes), text);
+            }
+        }
+
+        while let Some(open) = stack.pop() {
+            open.unwrap_into(top(&mut stack, &mut nodes));
+        }
+
+        Self(nodes)
+    }
+
+    /// Serialize the text back to the markup of CSL-JSON.
+    pub fn to_html(&self) -> String {
+        let mut buf = String::new();
+        write_rich_html(&mut buf, &self.0);
+        buf
+    }
+
+    /// Whether the text contains no markup.
+    pub fn is_plain(&self) -> bool {
+        self.0.iter().all(|node| matches!(node, RichNode::Text(_)))
+    }
+}
+
+impl std::fmt::Display for RichText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write(
+            f: &mut std::fmt::Formatter<'_>,
+            nodes: &[RichNode],
+        ) -> std::fmt::Result {
+            for node in nodes {
+                match node {
+                    RichNode::Text(text) => f.write_str(text)?,
+                    RichNode::Formatted(_, children) | RichNode::NoCase(children) => {
+                        write(f, children)?
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        write(f, &self.0)
+    }
+}
+
+/// A tag that is not closed yet while parsing rich text.
+struct OpenTag<'s> {
+    tag: Tag,
+    source: &'s str,
+    children: Vec<RichNode>,
+}
+
+impl OpenTag<'_> {
+    /// Add the tag as text and its content to the parent because it is not
+    /// properly closed.
+    fn unwrap_into(self, parent: &mut Vec<RichNode>) {
+        push_rich_text(parent, self.source);
+        for child in self.children {
+            match child {
+                RichNode::Text(text) => push_rich_text(parent, &text),
+                child => parent.push(child),
+            }
+        }
+    }
+}
+
+/// The content of the innermost open tag or the top level.
+fn top<'a>(
+    stack: &'a mut [OpenTag],
+    nodes: &'a mut Vec<RichNode>,
+) -> &'a mut Vec<RichNode> {
+    stack.last_mut().map_or(nodes, |open| &mut open.children)
+}
+
+/// A supported tag of the CSL-JSON markup.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tag {
+    Italic,
+    Bold,
+    Sup,
+    Sub,
+    SmallCaps,
+    NoCase,
+}
+
+impl Tag {
+    /// Parse the content of an opening tag.
+    fn parse(inner: &str) -> Option<Self> {
+        let compact: String = inner.chars().filter(|c| !c.is_whitespace()).collect();
+        Some(match compact.trim_end_matches(['/', ';']) {
+            "i" => Self::Italic,
+            "b" => Self::Bold,
+            "sup" => Self::Sup,
+            "sub" => Self::Sub,
+            "spanclass=\"nocase\"" => Self::NoCase,
+            "spanstyle=\"font-variant:small-caps\""
+            | "spanstyle=\"font-variant:small-caps;\"" => Self::SmallCaps,
+            _ => return None,
+        })
+    }
+
+    /// The name of the tag.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Italic => "i",
+            Self::Bold => "b",
+            Self::Sup => "sup",
+            Self::Sub => "sub",
+            Self::SmallCaps | Self::NoCase => "span",
+        }
+    }
+
+    /// Apply the tag to its content.
+    fn wrap(self, children: Vec<RichNode>) -> RichNode {
+        let mut formatting = Formatting::default();
+        match self {
+            Self::Italic => formatting.font_style = Some(FontStyle::Italic),
+            Self::Bold => formatting.font_weight = Some(FontWeight::Bold),
+            Self::Sup => formatting.vertical_align = Some(VerticalAlign::Sup),
+            Self::Sub => formatting.vertical_align = Some(VerticalAlign::Sub),
+            Self::SmallCaps => formatting.font_variant = Some(FontVariant::SmallCaps),
+            Self::NoCase => return RichNode::NoCase(children),
+        }
+        RichNode::Formatted(formatting, children)
+    }
+}
+
+/// Append text, merging it with a preceding text node.
+fn push_rich_text(nodes: &mut Vec<RichNode>, text: &str) {
+    match nodes.last_mut() {
+        Some(RichNode::Text(prev)) => prev.push_str(text),
+        _ => nodes.push(RichNode::Text(text.to_string())),
+    }
+}
+
+/// Decode a named or numeric character reference without `&` and `;`.
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+fn write_rich_html(buf: &mut String, nodes: &[RichNode]) {
+    for node in nodes {
+        match node {
+            RichNode::Text(text) => {
+                for c in text.chars() {
+                    match c {
+                        '&' => buf.push_str("&amp;"),
+                        '<' => buf.push_str("&lt;"),
+                        '>' => buf.push_str("&gt;"),
+                        c => buf.push(c),
+                    }
+                }
+            }
+            RichNode::NoCase(children) => {
+                buf.push_str("<span class=\"nocase\">");
+                write_rich_html(buf, children);
+                buf.push_str("</span>");
+            }
+            RichNode::Formatted(formatting, children) => {
+                let mut tags = Vec::new();
+                if formatting.font_style == Some(FontStyle::Italic) {
+                    tags.push(("<i>", "</i>"));
+                }
+                if formatting.font_weight == Some(FontWeight::Bold) {
+                    tags.push(("<b>", "</b>"));
+                }
+                if formatting.font_variant == Some(FontVariant::SmallCaps) {
+                    tags.push(("<span style=\"font-variant:small-caps;\">", "</span>"));
+                }
+                match formatting.vertical_align {
+                    Some(VerticalAlign::Sup) => tags.push(("<sup>", "</sup>")),
+                    Some(VerticalAlign::Sub) => tags.push(("<sub>", "</sub>")),
+                    _ => {}
+                }
+
+                for (open, _) in &tags {
+                    buf.push_str(open);
+                }
+                write_rich_html(buf, children);
+                for (_, close) in tags.iter().rev() {
+                    buf.push_str(close);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -492,10 +841,79 @@ mod tests {
                 raw: FixedDateRange::from_str("2021-09-10/2022-01-01").unwrap(),
                 literal: None,
                 season: None,
+                circa: false,
             }),
         );
 
         let item = Item(map);
         println!("{}", serde_json::to_string_pretty(&item).unwrap());
+
+        let source = r#"{"citationID": "c", "citationItems": [], "properties": {}}"#;
+        let citation: Citation = serde_json::from_str(source).unwrap();
+        let json = serde_json::to_string(&citation).unwrap();
+        assert!(json.starts_with(r#"{"citationId":"c","#));
+    }
+
+    #[test]
+    fn test_date_parts() {
+        let date = |parts: &[i16]| FixedDate::try_from(VecDate(parts.to_vec()));
+        let fixed = |month, day, season| FixedDate { year: 2020, month, day, season };
+
+        assert_eq!(date(&[]), Err(()));
+        assert_eq!(date(&[2020, 0]), Ok(fixed(None, None, None)));
+        assert_eq!(date(&[2020, 5, 40]), Ok(fixed(Some(4), None, None)));
+        assert_eq!(date(&[2020, 13, 2]), Ok(fixed(None, None, None)));
+        assert_eq!(date(&[2020, 22]), Ok(fixed(None, None, Some(Season::Summer))));
+        assert_eq!(date(&[2020, 12, 31]), Ok(fixed(Some(11), Some(30), None)));
+        assert_eq!(FixedDate::from_str("2020-00"), Err(()));
+        assert_eq!(FixedDate::from_str("2020-01-32"), Err(()));
+        assert_eq!(
+            FixedDate::from_str("2020-24"),
+            Ok(fixed(None, None, Some(Season::Winter)))
+        );
+    }
+
+    #[test]
+    fn rich_text() {
+        let source = "<b>Bold <i>and</i></b> <span style=\"font-variant: small-caps\">caps</span> \
+                      x<sup>2</sup> <span class=\"nocase\">iPhone</span> <u>a</u> </i> 1 &lt; 2 & <i>open";
+        let text = RichText::parse(source);
+        assert_eq!(
+            text.to_string(),
+            "Bold and caps x2 iPhone <u>a</u> </i> 1 < 2 & <i>open"
+        );
+        assert_eq!(
+            text.0[..2],
+            [
+                RichNode::Formatted(
+                    Formatting {
+                        font_weight: Some(FontWeight::Bold),
+                        ..Default::default()
+                    },
+                    vec![
+                        RichNode::Text("Bold ".into()),
+                        RichNode::Formatted(
+                            Formatting {
+                                font_style: Some(FontStyle::Italic),
+                                ..Default::default()
+                            },
+                            vec![RichNode::Text("and".into())]
+                        )
+                    ]
+                ),
+                RichNode::Text(" ".into()),
+            ]
+        );
+        assert_eq!(
+            text.to_html(),
+            "<b>Bold <i>and</i></b> <span style=\"font-variant:small-caps;\">caps</span> \
+             x<sup>2</sup> <span class=\"nocase\">iPhone</span> &lt;u&gt;a&lt;/u&gt; \
+             &lt;/i&gt; 1 &lt; 2 &amp; &lt;i&gt;open"
+        );
+        assert!(RichText::parse("Plain &amp; simple").is_plain());
+
+        let text = RichText::parse("x < y <i>z</i>");
+        assert_eq!(text.to_html(), "x &lt; y <i>z</i>");
+        assert_eq!(RichText::parse("a <b").to_string(), "a <b");
     }
 }
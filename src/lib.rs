@@ -4,11 +4,12 @@ A library for parsing CSL styles.
 Citationberg deserializes CSL styles from XML into Rust structs. It supports
 [CSL 1.0.2](https://docs.citationstyles.org/en/stable/specification.html).
 
-This crate is not a CSL processor, so you are free to choose whatever data
-model and data types you need for your bibliographic needs. If you need to
-render citations, you can use
+By default, this crate is not a CSL processor, so you are free to choose
+whatever data model and data types you need for your bibliographic needs. If
+you need to render citations, you can use
 [Hayagriva](https://github.com/typst/hayagriva) which uses this crate under
-the hood.
+the hood, or enable the `render` feature to render CSL-JSON items with the
+processor in the `render` module.
 
 Parse your style like this:
 
@@ -35,12 +36,20 @@ You can also parse a [`DependentStyle`] or a [`IndependentStyle`] directly.
 #![deny(missing_docs)]
 #![deny(unsafe_code)]
 
+pub mod analysis;
 #[cfg(feature = "json")]
 pub mod json;
+pub mod lint;
+pub mod locale;
+pub mod number;
+#[cfg(feature = "render")]
+pub mod render;
 pub mod taxonomy;
+pub mod visit;
 
 mod util;
 
+use std::borrow::Cow;
 use std::fmt::{self, Debug};
 use std::num::{NonZeroI16, NonZeroUsize};
 
@@ -65,6 +74,9 @@ pub type XmlSeResult<T> = Result<T, XmlSeError>;
 /// Error type for functions that serialize XML.
 pub type XmlSeError = quick_xml::se::SeError;
 
+/// Result type for functions that parse CSL files.
+pub type ParseResult<T> = Result<T, ParseError>;
+
 const EVENT_BUFFER_SIZE: Option<NonZeroUsize> = NonZeroUsize::new(u32::MAX as usize);
 
 /// Allow every struct with formatting properties to convert to a `Formatting`.
@@ -226,9 +238,8 @@ pub struct IndependentStyle {
 
 impl IndependentStyle {
     /// Create a style from an XML string.
-    pub fn from_xml(xml: &str) -> XmlDeResult<Self> {
-        let de = &mut deserializer(xml);
-        IndependentStyle::deserialize(de)
+    pub fn from_xml(xml: &str) -> ParseResult<Self> {
+        parse(xml)
     }
 
     /// Remove all non-required data that does not influence the style's
@@ -279,9 +290,8 @@ pub struct DependentStyle {
 
 impl DependentStyle {
     /// Create a style from an XML string.
-    pub fn from_xml(xml: &str) -> XmlDeResult<Self> {
-        let de = &mut deserializer(xml);
-        DependentStyle::deserialize(de)
+    pub fn from_xml(xml: &str) -> ParseResult<Self> {
+        parse(xml)
     }
 
     /// Remove all non-required data that does not influence the style's
@@ -289,8 +299,86 @@ impl DependentStyle {
     pub fn purge(&mut self, level: PurgeLevel) {
         self.info.purge(level);
     }
+
+    /// Merge this style with its parent into a standalone independent
+    /// style.
+    ///
+    /// The resolver is called with the URL of the parent link. The resulting
+    /// style takes its metadata from this style and its formatting rules
+    /// from the parent. If this style sets a default locale, it overrides
+    /// the parent's; the parent's locale overrides remain in effect.
+    pub fn resolve<R: StyleResolver>(
+        &self,
+        mut resolver: R,
+    ) -> Result<IndependentStyle, ResolveError> {
+        let url = &self.parent_link.href;
+        match resolver.resolve(url) {
+            Some(Style::Independent(parent)) => Ok(IndependentStyle {
+                info: self.info.clone(),
+                default_locale: self.default_locale.clone().or(parent.default_locale),
+                version: parent.version,
+                citation: parent.citation,
+                bibliography: parent.bibliography,
+                settings: parent.settings,
+                macros: parent.macros,
+                locale: parent.locale,
+            }),
+            Some(Style::Dependent(parent)) => Err(ResolveError::DependentParent {
+                parent: url.clone(),
+                grandparent: parent.parent_link.href,
+            }),
+            None => Err(ResolveError::MissingParent(url.clone())),
+        }
+    }
 }
 
+/// Retrieves the parent of a dependent style by its URL.
+///
+/// This is implemented for all closures of the form
+/// `FnMut(&str) -> Option<Style>`.
+pub trait StyleResolver {
+    /// Return the style with the given URL or `None` if it is unknown.
+    fn resolve(&mut self, url: &str) -> Option<Style>;
+}
+
+impl<F> StyleResolver for F
+where
+    F: FnMut(&str) -> Option<Style>,
+{
+    fn resolve(&mut self, url: &str) -> Option<Style> {
+        self(url)
+    }
+}
+
+/// An error that occurred while resolving a dependent style.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ResolveError {
+    /// The resolver did not return a style for the parent URL.
+    MissingParent(String),
+    /// The parent is itself a dependent style. CSL does not allow chains of
+    /// dependent styles.
+    DependentParent {
+        /// The URL of the parent style.
+        parent: String,
+        /// The URL the parent style links to.
+        grandparent: String,
+    },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingParent(url) => write!(f, "parent style {url} not found"),
+            Self::DependentParent { parent, grandparent } => write!(
+                f,
+                "parent style {parent} is a dependent style (linking to {grandparent})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
 impl<'de> Deserialize<'de> for DependentStyle {
     fn deserialize<D: serde::Deserializer<'de>>(
         deserializer: D,
@@ -319,9 +407,8 @@ pub enum Style {
 
 impl Style {
     /// Create a style from an XML string.
-    pub fn from_xml(xml: &str) -> XmlDeResult<Self> {
-        let de = &mut deserializer(xml);
-        Style::deserialize(de)
+    pub fn from_xml(xml: &str) -> ParseResult<Self> {
+        parse(xml)
     }
 
     /// Write the style to an XML string.
@@ -425,12 +512,128 @@ impl fmt::Display for StyleValidationError {
     }
 }
 
+/// An error that occurred while parsing a CSL file, together with the
+/// location in the source at which it occurred.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// The underlying deserialization error.
+    pub error: XmlDeError,
+    /// The byte offset in the source at which the error was detected.
+    pub offset: usize,
+    /// The 1-based line of `offset`.
+    pub line: usize,
+    /// The 1-based column of `offset`, counted in characters.
+    pub column: usize,
+    /// The path to the element that was being parsed when the error occurred.
+    pub path: ElementPath,
+}
+
+impl ParseError {
+    fn new<'de>(
+        xml: &str,
+        de: &Deserializer<'de, SliceReader<'de>>,
+        error: XmlDeError,
+    ) -> Self {
+        let reader = de.get_ref().get_ref();
+        let syntax_error = matches!(error, XmlDeError::InvalidXml(_));
+        let position =
+            if syntax_error { reader.error_position() } else { reader.buffer_position() }
+                as usize;
+
+        // Backticked parts of the message (like `@class` in "missing field
+        // `@class`" or the value in "unknown variant `foo`") help us find
+        // the offending element and attribute.
+        let message = error.to_string();
+        let hints: Vec<&str> = message.split('`').skip(1).step_by(2).collect();
+        let (path, element_start) = element_path(xml, position, &hints);
+
+        // The deserializer has usually read past the offending element, so
+        // point at its start instead.
+        let offset = if syntax_error { position } else { element_start };
+        let (line, column) = line_column(xml, offset);
+        Self { error, offset, line, column, path }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.error, self.line, self.column)?;
+        if !self.path.segments.is_empty() {
+            write!(f, " ({})", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<ParseError> for XmlDeError {
+    fn from(value: ParseError) -> Self {
+        value.error
+    }
+}
+
+/// The path to an element in a CSL file, such as
+/// `style/bibliography/layout/group[2]/text[1]@variable`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct ElementPath {
+    /// The elements from the root to the addressed element.
+    pub segments: Vec<PathSegment>,
+    /// The addressed attribute of the last element, if any.
+    pub attribute: Option<String>,
+}
+
+impl fmt::Display for ElementPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            write!(f, "{segment}")?;
+        }
+        if let Some(attribute) = &self.attribute {
+            write!(f, "@{attribute}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single element in an [`ElementPath`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PathSegment {
+    /// The element name without namespace prefix.
+    pub name: String,
+    /// The 1-based position among the siblings with the same name. `None` if
+    /// there are no such siblings.
+    pub index: Option<usize>,
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(index) = self.index {
+            write!(f, "[{index}]")?;
+        }
+        Ok(())
+    }
+}
+
 fn deserializer(xml: &str) -> Deserializer<'_, SliceReader<'_>> {
     let mut style_deserializer = Deserializer::from_str(xml);
     style_deserializer.event_buffer_size(EVENT_BUFFER_SIZE);
     style_deserializer
 }
 
+/// Deserialize a value from XML and locate any error in the source.
+fn parse<'de, T: Deserialize<'de>>(xml: &'de str) -> ParseResult<T> {
+    let mut de = deserializer(xml);
+    T::deserialize(&mut de).map_err(|err| ParseError::new(xml, &de, err))
+}
+
 /// A style with its own formatting rules.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub struct IndependentStyleSettings {
@@ -1772,6 +1975,24 @@ impl DatePart {
     pub fn form(&self) -> DateStrongAnyForm {
         DateStrongAnyForm::for_name(self.name, self.form)
     }
+
+    /// Override the attributes of this part with those of `other`, as done by
+    /// the date parts of a localized date in a style. The affixes are kept
+    /// because they are specific to the locale.
+    pub fn apply(&self, other: &Self) -> Self {
+        Self {
+            name: self.name,
+            form: other.form.or(self.form),
+            range_delimiter: other
+                .range_delimiter
+                .clone()
+                .or_else(|| self.range_delimiter.clone()),
+            formatting: other.formatting.apply(self.formatting),
+            affixes: self.affixes.clone(),
+            strip_periods: other.strip_periods || self.strip_periods,
+            text_case: other.text_case.or(self.text_case),
+        }
+    }
 }
 
 /// The kind of a date part with its `form` attribute.
@@ -2415,6 +2636,7 @@ pub struct InheritableNameOptions {
 
 /// Definite name options. Obtain from [`Name::options`] using
 /// [`InheritableNameOptions`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct NameOptions<'s> {
     /// Delimiter between second-to-last and last name.
     pub and: Option<NameAnd>,
@@ -2765,6 +2987,19 @@ impl Choose {
         std::iter::once(&self.if_).chain(self.else_if.iter())
     }
 
+    /// Select the children of the first branch that matches for a cite, or
+    /// of the `else` branch if none does. Returns `None` if no branch matches
+    /// and there is no `else` branch.
+    pub fn select(
+        &self,
+        ctx: &(impl CitationContext + ?Sized),
+    ) -> Option<&[LayoutRenderingElement]> {
+        match self.branches().find(|b| b.matches(ctx)) {
+            Some(branch) => Some(&branch.children),
+            None => self.otherwise.as_ref().map(|o| o.children.as_slice()),
+        }
+    }
+
     /// Find the child element that renders the given variable.
     pub fn find_variable_element(
         &self,
@@ -2848,6 +3083,40 @@ impl ChooseBranch {
             self.variable.as_ref().map(|variable| ChooseTest::Variable(variable))
         }
     }
+
+    /// Retrieve all tests of this branch. Valid CSL files must return at
+    /// least one test here.
+    pub fn tests(&self) -> impl Iterator<Item = ChooseTest<'_>> {
+        [
+            (self.disambiguate == Some(true)).then_some(ChooseTest::Disambiguate),
+            self.is_numeric.as_deref().map(ChooseTest::IsNumeric),
+            self.is_uncertain_date.as_deref().map(ChooseTest::IsUncertainDate),
+            self.locator.as_deref().map(ChooseTest::Locator),
+            self.position.as_deref().map(ChooseTest::Position),
+            self.type_.as_deref().map(ChooseTest::Type),
+            self.variable.as_deref().map(ChooseTest::Variable),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Retrieve the conditions of all tests of this branch, one for each
+    /// value of each test attribute.
+    pub fn conditions(&self) -> impl Iterator<Item = ChooseCondition> + '_ {
+        self.tests().flat_map(ChooseTest::conditions)
+    }
+
+    /// Check whether this branch matches. The callback decides whether a
+    /// single condition holds. The results are combined according to
+    /// [`Self::match_`].
+    pub fn evaluate(&self, check: impl FnMut(ChooseCondition) -> bool) -> bool {
+        self.match_.test(self.conditions().map(check))
+    }
+
+    /// Check whether this branch matches for a cite.
+    pub fn matches(&self, ctx: &(impl CitationContext + ?Sized)) -> bool {
+        self.evaluate(|condition| condition.holds(ctx))
+    }
 }
 
 /// The formatting instructions to use if no branch matches.
@@ -2879,6 +3148,130 @@ pub enum ChooseTest<'a> {
     Variable(&'a [Variable]),
 }
 
+impl<'a> ChooseTest<'a> {
+    /// Split the test into one condition for each of its values.
+    pub fn conditions(self) -> impl Iterator<Item = ChooseCondition> + 'a {
+        let conditions: Box<dyn Iterator<Item = ChooseCondition>> = match self {
+            Self::Disambiguate => {
+                Box::new(std::iter::once(ChooseCondition::Disambiguate))
+            }
+            Self::IsNumeric(v) => {
+                Box::new(v.iter().map(|&v| ChooseCondition::IsNumeric(v)))
+            }
+            Self::IsUncertainDate(v) => {
+                Box::new(v.iter().map(|&v| ChooseCondition::IsUncertainDate(v)))
+            }
+            Self::Locator(l) => Box::new(l.iter().map(|&l| ChooseCondition::Locator(l))),
+            Self::Position(p) => {
+                Box::new(p.iter().map(|&p| ChooseCondition::Position(p)))
+            }
+            Self::Type(k) => Box::new(k.iter().map(|&k| ChooseCondition::Type(k))),
+            Self::Variable(v) => {
+                Box::new(v.iter().map(|&v| ChooseCondition::Variable(v)))
+            }
+        };
+        conditions
+    }
+}
+
+/// A test of a conditional group for a single value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ChooseCondition {
+    /// Other than this choose, two elements would result in the same
+    /// rendering.
+    Disambiguate,
+    /// The variable contains numeric data.
+    IsNumeric(Variable),
+    /// The variable contains an approximate date.
+    IsUncertainDate(DateVariable),
+    /// The locator has the given type.
+    Locator(Locator),
+    /// The citation is in the given position.
+    Position(TestPosition),
+    /// The item has the given type.
+    Type(Kind),
+    /// The default form of the variable is non-empty.
+    Variable(Variable),
+}
+
+impl ChooseCondition {
+    /// Check whether the condition holds for a cite.
+    pub fn holds(self, ctx: &(impl CitationContext + ?Sized)) -> bool {
+        match self {
+            Self::Disambiguate => ctx.is_disambiguating(),
+            Self::IsNumeric(variable) => match variable {
+                Variable::Standard(_) | Variable::Number(_) | Variable::Page(_) => ctx
+                    .variable_text(variable)
+                    .is_some_and(|text| number::is_numeric(&text)),
+                Variable::Date(_) | Variable::Name(_) => false,
+            },
+            Self::IsUncertainDate(variable) => ctx.is_uncertain_date(variable),
+            Self::Locator(locator) => ctx.locator() == Some(locator),
+            Self::Position(position) => ctx
+                .position()
+                .is_some_and(|pos| position.matches(pos, ctx.is_near_note())),
+            Self::Type(kind) => ctx.kind() == kind,
+            Self::Variable(variable) => ctx.has_variable(variable),
+        }
+    }
+}
+
+/// Access to the data of a cite for evaluating conditions.
+///
+/// Implement this for your data model to evaluate [`Choose`] elements with
+/// [`Choose::select`] and [`ChooseBranch::matches`].
+pub trait CitationContext {
+    /// The type of the cited item.
+    fn kind(&self) -> Kind;
+
+    /// Whether the default form of the variable is non-empty.
+    fn has_variable(&self, variable: Variable) -> bool;
+
+    /// The value of a standard, number, or page variable as text. Used to
+    /// decide whether a variable is numeric.
+    fn variable_text(&self, variable: Variable) -> Option<Cow<'_, str>>;
+
+    /// Whether the date in the variable is approximate.
+    fn is_uncertain_date(&self, variable: DateVariable) -> bool;
+
+    /// The type of the cite's locator, if it has one.
+    fn locator(&self) -> Option<Locator> {
+        None
+    }
+
+    /// The position of the cite among the cites of the same item. `None` when
+    /// rendering the bibliography, where position tests never match.
+    fn position(&self) -> Option<CitePosition> {
+        None
+    }
+
+    /// Whether the cite is within `near-note-distance` of the previous cite
+    /// of the same item.
+    fn is_near_note(&self) -> bool {
+        false
+    }
+
+    /// Whether the cite is rendered to disambiguate it from another cite.
+    fn is_disambiguating(&self) -> bool {
+        false
+    }
+}
+
+/// The position of a cite among the cites of the same item.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CitePosition {
+    /// The first cite of the item.
+    First,
+    /// The item was cited before, but not directly before.
+    Subsequent,
+    /// The cite directly follows a cite of the same item with the same
+    /// locator.
+    Ibid,
+    /// The cite directly follows a cite of the same item with a different
+    /// locator.
+    IbidWithLocator,
+}
+
 /// Possible positions of a citation in the citations to the same item.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
@@ -2895,6 +3288,24 @@ pub enum TestPosition {
     NearNote,
 }
 
+impl TestPosition {
+    /// Whether a cite in the given position passes this test.
+    ///
+    /// As required by CSL, `ibid` also matches `ibid-with-locator` cites, and
+    /// `subsequent` matches all cites that are not the first.
+    pub fn matches(self, position: CitePosition, near_note: bool) -> bool {
+        match self {
+            Self::First => position == CitePosition::First,
+            Self::Subsequent => position != CitePosition::First,
+            Self::Ibid => {
+                matches!(position, CitePosition::Ibid | CitePosition::IbidWithLocator)
+            }
+            Self::IbidWithLocator => position == CitePosition::IbidWithLocator,
+            Self::NearNote => near_note && position != CitePosition::First,
+        }
+    }
+}
+
 /// How to handle the set of tests in a conditional group.
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
@@ -2957,9 +3368,8 @@ pub struct LocaleFile {
 
 impl LocaleFile {
     /// Create a locale from an XML string.
-    pub fn from_xml(xml: &str) -> XmlDeResult<Self> {
-        let locale: Self = quick_xml::de::from_str(xml)?;
-        Ok(locale)
+    pub fn from_xml(xml: &str) -> ParseResult<Self> {
+        parse(xml)
     }
 
     /// Write the locale to an XML string.
@@ -3158,10 +3568,10 @@ impl TryFrom<Locale> for LocaleFile {
     type Error = ();
 
     fn try_from(value: Locale) -> Result<Self, Self::Error> {
-        if value.lang.is_some() {
+        if let Some(lang) = value.lang {
             Ok(Self {
                 version: "1.0".to_string(),
-                lang: value.lang.unwrap(),
+                lang,
                 info: value.info,
                 terms: value.terms,
                 date: value.date,
@@ -3821,6 +4231,213 @@ mod test {
         assert_eq!("n11564–1568", run(min, "n11564 ", " 1568"));
     }
 
+    #[test]
+    fn parse_error_location() {
+        let style = |class: &str, last: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" {class} version="1.0">
+  <info>
+    <id/>
+    <title>Broken</title>
+  </info>
+  <citation>
+    <layout>
+      <text variable="title"/>
+    </layout>
+  </citation>
+  <bibliography>
+    <layout>
+      <group>
+        <text variable="title"/>
+      </group>
+      <group>
+        <text variable="title"/>
+        {last}
+      </group>
+    </layout>
+  </bibliography>
+</style>"#
+            )
+        };
+
+        let class = r#"class="in-text""#;
+        assert!(Style::from_xml(&style(class, r#"<text variable="title"/>"#)).is_ok());
+
+        let err =
+            Style::from_xml(&style(class, r#"<text variable="titel"/>"#)).unwrap_err();
+        assert_eq!((err.line, err.column), (19, 9));
+        assert_eq!(
+            err.path.to_string(),
+            "style/bibliography/layout/group[2]/text[2]@variable"
+        );
+
+        let err = Style::from_xml(&style(class, r#"<text term="nope"/>"#)).unwrap_err();
+        assert_eq!(
+            err.path.to_string(),
+            "style/bibliography/layout/group[2]/text[2]@term"
+        );
+
+        let err =
+            Style::from_xml(&style(class, r#"<date variable="isued"/>"#)).unwrap_err();
+        assert_eq!(
+            err.path.to_string(),
+            "style/bibliography/layout/group[2]/date@variable"
+        );
+
+        let err = Style::from_xml(&style("", r#"<text variable="title"/>"#)).unwrap_err();
+        assert_eq!(err.path.to_string(), "style");
+
+        let err = LocaleFile::from_xml("<locale>\n  <terms>\n</locale>").unwrap_err();
+        assert!(matches!(err.error, XmlDeError::InvalidXml(_)));
+        assert_eq!((err.line, err.column), (3, 1));
+        assert_eq!(err.path.to_string(), "locale/terms");
+    }
+
+    #[test]
+    fn resolve_dependent() {
+        let read = |path| fs::read_to_string(path).unwrap();
+        let child =
+            DependentStyle::from_xml(&read("tests/dependent/academic-questions.csl"))
+                .unwrap();
+        let parent =
+            IndependentStyle::from_xml(&read("tests/independent/ieee.csl")).unwrap();
+
+        let mut requested = None;
+        let resolved = child
+            .resolve(|url: &str| {
+                requested = Some(url.to_string());
+                Some(Style::Independent(parent.clone()))
+            })
+            .unwrap();
+        assert_eq!(
+            requested.as_deref(),
+            Some("http://www.zotero.org/styles/springer-humanities-brackets")
+        );
+        assert_eq!(resolved.info, child.info);
+        assert_eq!(resolved.default_locale, child.default_locale);
+        assert_eq!(resolved.citation, parent.citation);
+        assert_eq!(resolved.bibliography, parent.bibliography);
+        assert_eq!(resolved.macros, parent.macros);
+
+        let mut no_locale = child.clone();
+        no_locale.default_locale = None;
+        let mut parent = parent;
+        parent.default_locale = Some(LocaleCode("de-DE".into()));
+        let resolved = no_locale
+            .resolve(|_: &str| Some(Style::Independent(parent.clone())))
+            .unwrap();
+        assert_eq!(resolved.default_locale, Some(LocaleCode("de-DE".into())));
+
+        assert_eq!(
+            child.resolve(|_: &str| None),
+            Err(ResolveError::MissingParent(child.parent_link.href.clone()))
+        );
+
+        let other = DependentStyle::from_xml(&read(
+            "tests/dependent/accounts-of-chemical-research.csl",
+        ))
+        .unwrap();
+        assert_eq!(
+            child.resolve(|_: &str| Some(Style::Dependent(other.clone()))),
+            Err(ResolveError::DependentParent {
+                parent: child.parent_link.href.clone(),
+                grandparent: "http://www.zotero.org/styles/american-chemical-society"
+                    .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn choose_branch_tests() {
+        let branch: ChooseBranch = quick_xml::de::from_str(
+            r#"<if type="book chapter" variable="editor" position="first" match="any"/>"#,
+        )
+        .unwrap();
+
+        let tests: Vec<_> = branch.tests().collect();
+        assert_eq!(
+            tests,
+            [
+                ChooseTest::Position(&[TestPosition::First]),
+                ChooseTest::Type(&[Kind::Book, Kind::Chapter]),
+                ChooseTest::Variable(&[Variable::Name(NameVariable::Editor)]),
+            ]
+        );
+        assert_eq!(branch.conditions().count(), 4);
+
+        let is_chapter = |c| c == ChooseCondition::Type(Kind::Chapter);
+        assert!(branch.evaluate(is_chapter));
+        assert!(!branch.evaluate(|_| false));
+
+        let all = ChooseBranch { match_: ChooseMatch::All, ..branch.clone() };
+        assert!(!all.evaluate(is_chapter));
+        assert!(all.evaluate(|_| true));
+
+        let none = ChooseBranch { match_: ChooseMatch::None, ..branch };
+        assert!(!none.evaluate(is_chapter));
+        assert!(none.evaluate(|_| false));
+    }
+
+    #[test]
+    fn choose_select() {
+        struct Cite {
+            position: Option<CitePosition>,
+            volume: &'static str,
+        }
+
+        impl CitationContext for Cite {
+            fn kind(&self) -> Kind {
+                Kind::Book
+            }
+
+            fn has_variable(&self, variable: Variable) -> bool {
+                variable == Variable::Number(taxonomy::NumberVariable::Volume)
+            }
+
+            fn variable_text(&self, variable: Variable) -> Option<Cow<'_, str>> {
+                self.has_variable(variable).then_some(self.volume.into())
+            }
+
+            fn is_uncertain_date(&self, _: DateVariable) -> bool {
+                false
+            }
+
+            fn position(&self) -> Option<CitePosition> {
+                self.position
+            }
+        }
+
+        let choose: Choose = quick_xml::de::from_str(
+            r#"<choose>
+  <if position="ibid"><text term="ibid"/></if>
+  <else-if is-numeric="volume" type="book"><text variable="volume"/></else-if>
+  <else><text variable="title"/></else>
+</choose>"#,
+        )
+        .unwrap();
+
+        let select = |position, volume| {
+            let Some([LayoutRenderingElement::Text(text)]) =
+                choose.select(&Cite { position, volume })
+            else {
+                panic!("expected a single text element");
+            };
+            text.target.clone()
+        };
+
+        let ibid = TextTarget::from(Term::Other(OtherTerm::Ibid));
+        assert_eq!(select(Some(CitePosition::IbidWithLocator), "2"), ibid);
+        assert_eq!(
+            select(Some(CitePosition::Subsequent), "2"),
+            TextTarget::from(Variable::Number(taxonomy::NumberVariable::Volume))
+        );
+        assert_eq!(
+            select(None, "2nd edition"),
+            TextTarget::from(Variable::Standard(taxonomy::StandardVariable::Title))
+        );
+    }
+
     #[test]
     fn locale_in_style_file() {
         let style_str = r#"<style 
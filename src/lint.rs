@@ -0,0 +1,542 @@
+//! Semantic checks for independent styles.
+//!
+//! Parsing only ensures that a style is structurally valid. The checks in this
+//! module find problems that would only show up when rendering citations, like
+//! calls to macros that don't exist. Run them with [`IndependentStyle::lint`].
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::taxonomy::Variable;
+use crate::{
+    ChooseBranch, CslMacro, ElementPath, IndependentStyle, Layout,
+    LayoutRenderingElement, Names, NamesChild, PathSegment, Sort, SortKey, TextTarget,
+};
+
+/// A problem found in a style.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The kind of problem.
+    pub code: DiagnosticCode,
+    /// A human-readable description of the problem.
+    pub message: String,
+    /// The element at which the problem occurred.
+    pub path: ElementPath,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {} ({})", self.severity, self.code, self.message, self.path)
+    }
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// The style will likely render something other than what its author
+    /// intended.
+    Warning,
+    /// The style violates the CSL specification.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// The kind of problem a [`Diagnostic`] describes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum DiagnosticCode {
+    /// A `cs:text` element calls a macro that is not defined.
+    UndefinedMacro,
+    /// Two macros share the same name.
+    DuplicateMacro,
+    /// A macro calls itself, directly or through other macros.
+    RecursiveMacro,
+    /// A sort key references a macro that is not defined.
+    UndefinedSortMacro,
+    /// A `cs:label` element labels a variable that is not rendered next to it.
+    LabelWithoutVariable,
+    /// A `cs:names` element can never render anything.
+    UnrenderableNames,
+}
+
+impl DiagnosticCode {
+    /// A stable, kebab-case identifier for the code.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::UndefinedMacro => "undefined-macro",
+            Self::DuplicateMacro => "duplicate-macro",
+            Self::RecursiveMacro => "recursive-macro",
+            Self::UndefinedSortMacro => "undefined-sort-macro",
+            Self::LabelWithoutVariable => "label-without-variable",
+            Self::UnrenderableNames => "unrenderable-names",
+        }
+    }
+
+    /// The severity with which this kind of problem is reported.
+    pub const fn severity(self) -> Severity {
+        match self {
+            Self::UndefinedMacro
+            | Self::DuplicateMacro
+            | Self::RecursiveMacro
+            | Self::UndefinedSortMacro => Severity::Error,
+            Self::LabelWithoutVariable | Self::UnrenderableNames => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl IndependentStyle {
+    /// Check the style for semantic problems.
+    ///
+    /// The diagnostics are returned in document order within each check.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut linter = Linter {
+            macros: &self.macros,
+            macro_body: &[],
+            path: vec![segment("style", None)],
+            diagnostics: Vec::new(),
+        };
+
+        linter.check_macro_definitions(self.macro_graph().cycles());
+
+        linter.enter("citation", None);
+        if let Some(sort) = &self.citation.sort {
+            linter.check_sort(sort);
+        }
+        linter.check_layout(&self.citation.layout);
+        linter.leave();
+
+        if let Some(bibliography) = &self.bibliography {
+            linter.enter("bibliography", None);
+            if let Some(sort) = &bibliography.sort {
+                linter.check_sort(sort);
+            }
+            linter.check_layout(&bibliography.layout);
+            linter.leave();
+        }
+
+        let indices = sibling_indices(self.macros.iter().map(|_| "macro"));
+        for (m, idx) in self.macros.iter().zip(indices) {
+            linter.enter("macro", idx);
+            linter.macro_body = &m.children;
+            linter.check_children(&m.children, &m.children);
+            linter.macro_body = &[];
+            linter.leave();
+        }
+
+        linter.diagnostics
+    }
+}
+
+struct Linter<'a> {
+    macros: &'a [CslMacro],
+    /// The children of the macro that is being checked, if any.
+    macro_body: &'a [LayoutRenderingElement],
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn enter(&mut self, name: &str, index: Option<usize>) {
+        self.path.push(segment(name, index));
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    fn report(&mut self, code: DiagnosticCode, message: String, attribute: Option<&str>) {
+        self.diagnostics.push(Diagnostic {
+            severity: code.severity(),
+            code,
+            message,
+            path: ElementPath {
+                segments: self.path.clone(),
+                attribute: attribute.map(ToString::to_string),
+            },
+        });
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.iter().any(|m| m.name == name)
+    }
+
+    /// Check for duplicate and recursive macros.
+    fn check_macro_definitions(&mut self, cycles: Vec<Vec<&str>>) {
+        let indices = sibling_indices(self.macros.iter().map(|_| "macro"));
+        let mut seen = HashSet::new();
+        for (m, &idx) in self.macros.iter().zip(&indices) {
+            if !seen.insert(m.name.as_str()) {
+                self.enter("macro", idx);
+                self.report(
+                    DiagnosticCode::DuplicateMacro,
+                    format!("macro `{}` is defined more than once", m.name),
+                    Some("name"),
+                );
+                self.leave();
+            }
+        }
+
+        for cycle in cycles {
+            let Some(position) = self.macros.iter().position(|m| m.name == cycle[0])
+            else {
+                continue;
+            };
+
+            self.enter("macro", indices[position]);
+            self.report(
+                DiagnosticCode::RecursiveMacro,
+                format!(
+                    "macro `{}` calls itself via {}",
+                    cycle[0],
+                    describe_cycle(&cycle)
+                ),
+                Some("name"),
+            );
+            self.leave();
+        }
+    }
+
+    fn check_sort(&mut self, sort: &Sort) {
+        self.enter("sort", None);
+        let indices = sibling_indices(sort.keys.iter().map(|_| "key"));
+        for (key, idx) in sort.keys.iter().zip(indices) {
+            if let SortKey::MacroName { name, .. } = key
+                && !self.is_defined(name)
+            {
+                self.enter("key", idx);
+                self.report(
+                    DiagnosticCode::UndefinedSortMacro,
+                    format!("sort key references undefined macro `{name}`"),
+                    Some("macro"),
+                );
+                self.leave();
+            }
+        }
+        self.leave();
+    }
+
+    fn check_layout(&mut self, layout: &Layout) {
+        self.enter("layout", None);
+        self.check_children(&layout.elements, &layout.elements);
+        self.leave();
+    }
+
+    /// Check a list of elements. The `scope` contains the children of the
+    /// closest enclosing element that is not a conditional branch.
+    fn check_children(
+        &mut self,
+        children: &[LayoutRenderingElement],
+        scope: &[LayoutRenderingElement],
+    ) {
+        let indices = sibling_indices(children.iter().map(element_name));
+        for (child, idx) in children.iter().zip(indices) {
+            self.enter(element_name(child), idx);
+            self.check_element(child, scope);
+            self.leave();
+        }
+    }
+
+    fn check_element(
+        &mut self,
+        element: &LayoutRenderingElement,
+        scope: &[LayoutRenderingElement],
+    ) {
+        match element {
+            LayoutRenderingElement::Text(text) => {
+                if let TextTarget::Macro { name } = &text.target
+                    && !self.is_defined(name)
+                {
+                    self.report(
+                        DiagnosticCode::UndefinedMacro,
+                        format!("call to undefined macro `{name}`"),
+                        Some("macro"),
+                    );
+                }
+            }
+            LayoutRenderingElement::Label(label) => {
+                let variable = Variable::from(label.variable);
+                // The top level of a macro is grouped with the elements next
+                // to its calls, so its labels cannot be checked on their own.
+                let in_macro_body = std::ptr::eq(scope, self.macro_body);
+                let mut called = HashSet::new();
+                if !in_macro_body
+                    && !scope
+                        .iter()
+                        .any(|s| self.renders_variable(s, variable, &mut called))
+                {
+                    self.report(
+                        DiagnosticCode::LabelWithoutVariable,
+                        format!(
+                            "label for `{variable}` is not grouped with an element \
+                             rendering that variable"
+                        ),
+                        Some("variable"),
+                    );
+                }
+            }
+            LayoutRenderingElement::Names(names) => self.check_names(names),
+            LayoutRenderingElement::Group(group) => {
+                self.check_children(&group.children, &group.children)
+            }
+            LayoutRenderingElement::Choose(choose) => {
+                let branches: Vec<(&str, &ChooseBranch)> =
+                    std::iter::once(("if", &choose.if_))
+                        .chain(choose.else_if.iter().map(|b| ("else-if", b)))
+                        .collect();
+                let indices = sibling_indices(branches.iter().map(|(name, _)| *name));
+                for ((name, branch), idx) in branches.iter().zip(indices) {
+                    self.enter(name, idx);
+                    self.check_children(&branch.children, scope);
+                    self.leave();
+                }
+                if let Some(otherwise) = &choose.otherwise {
+                    self.enter("else", None);
+                    self.check_children(&otherwise.children, scope);
+                    self.leave();
+                }
+            }
+            LayoutRenderingElement::Date(_) | LayoutRenderingElement::Number(_) => {}
+        }
+    }
+
+    /// Whether an element renders the given variable, directly or through
+    /// the macros it calls. Calls to undefined macros count as rendering the
+    /// variable because they are reported on their own. Each macro is only
+    /// followed once so that recursive macros terminate.
+    fn renders_variable<'m>(
+        &self,
+        element: &'m LayoutRenderingElement,
+        variable: Variable,
+        called: &mut HashSet<&'m str>,
+    ) -> bool
+    where
+        'a: 'm,
+    {
+        match element {
+            LayoutRenderingElement::Text(text) => match &text.target {
+                TextTarget::Variable { var, .. } => *var == variable,
+                TextTarget::Macro { name } => {
+                    if !self.is_defined(name) {
+                        return true;
+                    }
+                    called.insert(name)
+                        && self
+                            .macros
+                            .iter()
+                            .filter(|m| m.name == *name)
+                            .flat_map(|m| &m.children)
+                            .any(|c| self.renders_variable(c, variable, called))
+                }
+                TextTarget::Term { .. } | TextTarget::Value { .. } => false,
+            },
+            LayoutRenderingElement::Number(number) => {
+                Variable::from(number.variable) == variable
+            }
+            LayoutRenderingElement::Date(_)
+            | LayoutRenderingElement::Names(_)
+            | LayoutRenderingElement::Label(_) => false,
+            LayoutRenderingElement::Group(group) => group
+                .children
+                .iter()
+                .any(|c| self.renders_variable(c, variable, called)),
+            LayoutRenderingElement::Choose(choose) => choose
+                .branches()
+                .flat_map(|b| &b.children)
+                .chain(choose.otherwise.iter().flat_map(|o| &o.children))
+                .any(|c| self.renders_variable(c, variable, called)),
+        }
+    }
+
+    fn check_names(&mut self, names: &Names) {
+        let substitute = names.substitute();
+        if names.variable.is_empty() && substitute.is_none_or(|s| s.children.is_empty()) {
+            self.report(
+                DiagnosticCode::UnrenderableNames,
+                "names element has neither a variable nor a substitute".to_string(),
+                None,
+            );
+        }
+
+        let indices = sibling_indices(names.children.iter().map(names_child_name));
+        for (child, idx) in names.children.iter().zip(indices) {
+            if let NamesChild::Substitute(substitute) = child {
+                self.enter("substitute", idx);
+                self.check_children(&substitute.children, &substitute.children);
+                self.leave();
+            }
+        }
+    }
+}
+
+fn describe_cycle(cycle: &[&str]) -> String {
+    let mut out = String::new();
+    for name in cycle.iter().skip(1).chain(cycle.first()) {
+        if !out.is_empty() {
+            out.push_str(" → ");
+        }
+        out.push('`');
+        out.push_str(name);
+        out.push('`');
+    }
+    out
+}
+
+/// The CSL element name of a rendering element.
+pub(crate) fn element_name(element: &LayoutRenderingElement) -> &'static str {
+    match element {
+        LayoutRenderingElement::Text(_) => "text",
+        LayoutRenderingElement::Date(_) => "date",
+        LayoutRenderingElement::Number(_) => "number",
+        LayoutRenderingElement::Names(_) => "names",
+        LayoutRenderingElement::Label(_) => "label",
+        LayoutRenderingElement::Group(_) => "group",
+        LayoutRenderingElement::Choose(_) => "choose",
+    }
+}
+
+fn names_child_name(child: &NamesChild) -> &'static str {
+    match child {
+        NamesChild::Name(_) => "name",
+        NamesChild::EtAl(_) => "et-al",
+        NamesChild::Label(_) => "label",
+        NamesChild::Substitute(_) => "substitute",
+    }
+}
+
+fn segment(name: &str, index: Option<usize>) -> PathSegment {
+    PathSegment { name: name.to_string(), index }
+}
+
+/// Compute the path index of each sibling from the element names. The index
+/// is the 1-based position among the siblings with the same name, or `None`
+/// if the name is unique.
+fn sibling_indices<'a>(names: impl Iterator<Item = &'a str>) -> Vec<Option<usize>> {
+    let names: Vec<&str> = names.collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name).or_default() += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    names
+        .iter()
+        .map(|name| {
+            let position = seen.entry(name).or_default();
+            *position += 1;
+            (counts[name] > 1).then_some(*position)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lint_style() {
+        let style = IndependentStyle::from_xml(
+            r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><id/><title/></info>
+  <macro name="author">
+    <names variable="author">
+      <substitute><text macro="editor"/></substitute>
+    </names>
+  </macro>
+  <macro name="editor">
+    <text macro="author"/>
+  </macro>
+  <macro name="editor">
+    <names/>
+  </macro>
+  <macro name="pages">
+    <choose><if variable="page"><number variable="page"/></if></choose>
+  </macro>
+  <macro name="issue">
+    <text macro="issue"/>
+    <text variable="issue"/>
+  </macro>
+  <macro name="page-label">
+    <label variable="page"/>
+  </macro>
+  <citation>
+    <sort><key macro="year"/></sort>
+    <layout>
+      <text macro="author"/>
+      <group>
+        <label variable="page"/>
+        <text macro="title"/>
+      </group>
+      <group>
+        <label variable="page"/>
+        <number variable="page"/>
+      </group>
+      <group>
+        <label variable="volume"/>
+        <text variable="page"/>
+      </group>
+      <group>
+        <label variable="page"/>
+        <text macro="pages"/>
+      </group>
+      <group>
+        <label variable="page"/>
+        <text macro="issue"/>
+      </group>
+      <group>
+        <text macro="page-label"/>
+        <text variable="page"/>
+      </group>
+    </layout>
+  </citation>
+</style>"#,
+        )
+        .unwrap();
+
+        let diagnostics: Vec<_> = style
+            .lint()
+            .into_iter()
+            .map(|d| (d.code, d.path.to_string()))
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            [
+                (DiagnosticCode::DuplicateMacro, "style/macro[3]@name".to_string()),
+                (DiagnosticCode::RecursiveMacro, "style/macro[1]@name".to_string()),
+                (DiagnosticCode::RecursiveMacro, "style/macro[5]@name".to_string()),
+                (
+                    DiagnosticCode::UndefinedSortMacro,
+                    "style/citation/sort/key@macro".to_string()
+                ),
+                (
+                    DiagnosticCode::UndefinedMacro,
+                    "style/citation/layout/group[1]/text@macro".to_string()
+                ),
+                (
+                    DiagnosticCode::LabelWithoutVariable,
+                    "style/citation/layout/group[3]/label@variable".to_string()
+                ),
+                (
+                    DiagnosticCode::LabelWithoutVariable,
+                    "style/citation/layout/group[5]/label@variable".to_string()
+                ),
+                (DiagnosticCode::UnrenderableNames, "style/macro[3]/names".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Resolution of localization data across style locales and locale files.
+//!
+//! CSL looks up terms, date formats and locale options in a fixed order: first
+//! in the style's `cs:locale` elements for the requested dialect, the
+//! requested language, and without a language, then in the locale file for
+//! the requested dialect, the locale file for the language's primary dialect,
+//! and finally in the `en-US` locale file. A [`LocaleResolver`] performs this
+//! cascade.
+
+use crate::taxonomy::{Locator, Term};
+use crate::{
+    Date, DateForm, GrammarGender, IndependentStyle, Locale, LocaleCode, LocaleFile,
+    LocaleOptions, LocalizedTerm, OrdinalLookup, TermForm,
+};
+
+/// Looks up localization data in the order prescribed by CSL.
+#[derive(Debug, Clone)]
+pub struct LocaleResolver<'a> {
+    lang: Option<LocaleCode>,
+    sources: Vec<Source<'a>>,
+}
+
+/// The localization data of a single style locale or locale file.
+#[derive(Debug, Clone, Copy)]
+struct Source<'a> {
+    terms: &'a [LocalizedTerm],
+    dates: &'a [Date],
+    options: Option<&'a LocaleOptions>,
+}
+
+impl<'a> From<&'a Locale> for Source<'a> {
+    fn from(locale: &'a Locale) -> Self {
+        Self {
+            terms: locale.terms.as_ref().map_or(&[], |t| &t.terms),
+            dates: &locale.date,
+            options: locale.style_options.as_ref(),
+        }
+    }
+}
+
+impl<'a> From<&'a LocaleFile> for Source<'a> {
+    fn from(file: &'a LocaleFile) -> Self {
+        Self {
+            terms: file.terms.as_ref().map_or(&[], |t| &t.terms),
+            dates: &file.date,
+            options: file.style_options.as_ref(),
+        }
+    }
+}
+
+impl<'a> LocaleResolver<'a> {
+    /// Create a resolver for the requested locale from the style's inline
+    /// locales and the available locale files.
+    pub fn new(
+        requested: &LocaleCode,
+        style_locales: &'a [Locale],
+        files: &'a [LocaleFile],
+    ) -> Self {
+        let base = requested.parse_base();
+        let mut style_langs = vec![Some(requested.0.as_str())];
+        if let Some(base) = &base
+            && base.as_str() != requested.0
+        {
+            style_langs.push(Some(base.as_str()));
+        }
+        style_langs.push(None);
+
+        let mut sources = Vec::new();
+        for lang in style_langs {
+            sources.extend(
+                style_locales
+                    .iter()
+                    .filter(|l| l.lang.as_ref().map(|l| l.0.as_str()) == lang)
+                    .map(Source::from),
+            );
+        }
+
+        let mut file_langs: Vec<LocaleCode> = vec![];
+        let candidates =
+            [Some(requested.clone()), requested.fallback(), Some(LocaleCode::en_us())];
+        for lang in candidates.into_iter().flatten() {
+            if !file_langs.contains(&lang) {
+                sources.extend(files.iter().find(|f| f.lang == lang).map(Source::from));
+                file_langs.push(lang);
+            }
+        }
+
+        Self { lang: Some(requested.clone()), sources }
+    }
+
+    /// The language of the requested locale, if known.
+    pub fn lang(&self) -> Option<&LocaleCode> {
+        self.lang.as_ref()
+    }
+
+    /// Get the first translation of a term in exactly the given form.
+    pub fn term(&self, term: Term, form: TermForm) -> Option<&'a LocalizedTerm> {
+        self.sources.iter().find_map(|s| {
+            s.terms
+                .iter()
+                .find(|t| t.name.is_lexically_same(term) && t.form == form)
+        })
+    }
+
+    /// Get a term translation, falling back to other forms as prescribed by
+    /// [`TermForm::fallback`] if the requested form is not defined.
+    ///
+    /// If a gender is given, a variant with a matching `gender-form` is
+    /// preferred over the neutral one of the same source. A neutral variant
+    /// in a source with a higher priority wins over a gendered one in a
+    /// source with a lower priority.
+    pub fn term_or_fallback(
+        &self,
+        term: Term,
+        form: TermForm,
+        gender: Option<GrammarGender>,
+    ) -> Option<&'a LocalizedTerm> {
+        let mut form = Some(form);
+        while let Some(f) = form {
+            let found = self.sources.iter().find_map(|s| {
+                let mut genders = gender.map(Some).into_iter().chain([None]);
+                genders.find_map(|g| {
+                    s.terms.iter().find(|t| {
+                        t.name.is_lexically_same(term)
+                            && t.form == f
+                            && t.gender_form == g
+                    })
+                })
+            });
+
+            if found.is_some() {
+                return found;
+            }
+
+            form = f.fallback();
+        }
+
+        None
+    }
+
+    /// Get the singular or plural string for a term, using the same fallback
+    /// as [`Self::term_or_fallback`].
+    pub fn localize(
+        &self,
+        term: Term,
+        form: TermForm,
+        plural: bool,
+        gender: Option<GrammarGender>,
+    ) -> Option<&'a str> {
+        let term = self.term_or_fallback(term, form, gender)?;
+        if plural { term.multiple() } else { term.single() }
+    }
+
+    /// Get the grammatical gender of a term, as specified by its long form.
+    pub fn gender(&self, term: Term) -> Option<GrammarGender> {
+        self.term_or_fallback(term, TermForm::Long, None)?.gender
+    }
+
+    /// Get the localized date format of the given form.
+    pub fn date(&self, form: DateForm) -> Option<&'a Date> {
+        self.sources
+            .iter()
+            .find_map(|s| s.dates.iter().find(|d| d.form == Some(form)))
+    }
+
+    /// Get the locale options. Each option is taken from the first locale
+    /// that sets it.
+    pub fn options(&self) -> LocaleOptions {
+        let mut options = LocaleOptions {
+            limit_day_ordinals_to_day_1: None,
+            punctuation_in_quote: None,
+        };
+
+        for o in self.sources.iter().filter_map(|s| s.options) {
+            options.limit_day_ordinals_to_day_1 =
+                options.limit_day_ordinals_to_day_1.or(o.limit_day_ordinals_to_day_1);
+            options.punctuation_in_quote =
+                options.punctuation_in_quote.or(o.punctuation_in_quote);
+        }
+
+        options
+    }
+
+    /// Split a locator typed by a user, like "p. 12" or "§§ 4–6", into its
+    /// kind and its value.
+    ///
+    /// The label at the start of the text is matched against the singular
+    /// and plural variants of the locator terms in all forms, ignoring case.
+    /// The longest matching label wins. Returns `None` if the text does not
+    /// start with a known label, in which case CSL-JSON treats the whole
+    /// text as a page locator.
+    ///
+    /// ```
+    /// use citationberg::LocaleFile;
+    /// use citationberg::locale::LocaleResolver;
+    /// use citationberg::taxonomy::Locator;
+    ///
+    /// let file = LocaleFile::from_xml(&std::fs::read_to_string(
+    ///     "tests/locales/locales-en-US.xml",
+    /// )?)?;
+    /// let resolver = LocaleResolver::new(&file.lang, &[], std::slice::from_ref(&file));
+    ///
+    /// assert_eq!(resolver.parse_locator("pp. 12-14"), Some((Locator::Page, "12-14")));
+    /// assert_eq!(resolver.parse_locator("§§ 4–6"), Some((Locator::Section, "4–6")));
+    /// assert_eq!(resolver.parse_locator("12"), None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parse_locator<'s>(&self, text: &'s str) -> Option<(Locator, &'s str)> {
+        let text = text.trim_start();
+        let mut best: Option<(Locator, usize)> = None;
+        for term in self.sources.iter().flat_map(|s| s.terms) {
+            let Some(locator) = locator_of(term.name) else { continue };
+            for label in [term.single(), term.multiple()].into_iter().flatten() {
+                let len = label.len();
+                if len > best.map_or(0, |(_, len)| len) && starts_with_label(text, label)
+                {
+                    best = Some((locator, len));
+                }
+            }
+        }
+
+        let (locator, len) = best?;
+        Some((locator, text[len..].trim()))
+    }
+
+    /// Retrieve the ordinal terms for number lookups.
+    ///
+    /// Ordinal terms are not merged: the first locale that defines any
+    /// ordinal term replaces all ordinal terms of the following ones.
+    pub fn ordinals(&self) -> OrdinalLookup<'a> {
+        self.sources
+            .iter()
+            .find(|s| s.terms.iter().any(|t| t.name.is_ordinal()))
+            .map_or(OrdinalLookup::empty(), |s| {
+                OrdinalLookup::new(s.terms.iter().filter(|t| t.name.is_ordinal()))
+            })
+    }
+}
+
+/// The locator that a term names. Some locator terms share their name with a
+/// variable or an item type and are parsed as those.
+fn locator_of(term: Term) -> Option<Locator> {
+    use Locator::*;
+    match term {
+        Term::Locator(locator) => Some(locator),
+        _ => [Book, Chapter, Figure, Issue, Page, Section, Volume]
+            .into_iter()
+            .find(|&locator| term.is_lexically_same(locator.into())),
+    }
+}
+
+/// Whether a text starts with a label, ignoring case. A label that ends in a
+/// letter or digit must not be followed by another one, so that "p" does not
+/// match "pp. 4".
+fn starts_with_label(text: &str, label: &str) -> bool {
+    let Some(start) = text.get(..label.len()) else { return false };
+    if label.trim().is_empty() || start.to_lowercase() != label.to_lowercase() {
+        return false;
+    }
+
+    let ends_in_word = label.chars().next_back().is_some_and(char::is_alphanumeric);
+    let next = text[label.len()..].chars().next();
+    !(ends_in_word && next.is_some_and(char::is_alphanumeric))
+}
+
+impl<'a> From<&'a Locale> for LocaleResolver<'a> {
+    fn from(locale: &'a Locale) -> Self {
+        Self {
+            lang: locale.lang.clone(),
+            sources: vec![Source::from(locale)],
+        }
+    }
+}
+
+impl IndependentStyle {
+    /// Create a resolver for this style's localization data.
+    ///
+    /// Uses the style's default locale if no locale is requested and `en-US`
+    /// if the style has no default locale either.
+    pub fn locale_resolver<'a>(
+        &'a self,
+        requested: Option<&LocaleCode>,
+        files: &'a [LocaleFile],
+    ) -> LocaleResolver<'a> {
+        let en_us = LocaleCode::en_us();
+        let requested = requested.or(self.default_locale.as_ref()).unwrap_or(&en_us);
+        LocaleResolver::new(requested, &self.locale, files)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::taxonomy::{Locator, NameVariable, NumberVariable, OtherTerm, Term};
+
+    fn locale(lang: Option<&str>, terms: &str) -> Locale {
+        let lang = lang.map(|l| format!(r#" xml:lang="{l}""#)).unwrap_or_default();
+        let style = format!(
+            r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><id/><title/><updated>2024-01-01T00:00:00+00:00</updated></info>
+  <locale{lang}><terms>{terms}</terms></locale>
+  <citation><layout><text variable="title"/></layout></citation>
+</style>"#
+        );
+        IndependentStyle::from_xml(&style).unwrap().locale.remove(0)
+    }
+
+    fn file(lang: &str, terms: &str) -> LocaleFile {
+        LocaleFile::from_xml(&format!(
+            r#"<locale xmlns="http://purl.org/net/xbiblio/csl" version="1.0" xml:lang="{lang}">
+  <style-options punctuation-in-quote="{}"/>
+  <terms>{terms}</terms>
+</locale>"#,
+            lang == "en-US"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn cascade() {
+        let and = Term::Other(OtherTerm::And);
+        let in_ = Term::Other(OtherTerm::In);
+        let et_al = Term::Other(OtherTerm::EtAl);
+        let style = [
+            locale(
+                None,
+                r#"<term name="and">untagged</term><term name="in">im</term>
+<term name="ordinal">th</term>"#,
+            ),
+            locale(Some("de"), r#"<term name="and">language</term>"#),
+            locale(Some("de-AT"), r#"<term name="and">dialect</term>"#),
+            locale(Some("fr"), r#"<term name="et-al">et coll.</term>"#),
+        ];
+        let files = [
+            file(
+                "en-US",
+                r#"<term name="and">and</term><term name="et-al">et al.</term>
+<term name="ordinal" gender-form="feminine">re</term>"#,
+            ),
+            file("de-AT", r#"<term name="in">in</term>"#),
+            file(
+                "de-DE",
+                r#"<term name="and">und</term><term name="et-al">u. a.</term>"#,
+            ),
+        ];
+
+        let get = |lang: &str, term| {
+            LocaleResolver::new(&LocaleCode(lang.into()), &style, &files)
+                .term(term, TermForm::Long)
+                .and_then(LocalizedTerm::single)
+        };
+
+        assert_eq!(get("de-AT", and), Some("dialect"));
+        assert_eq!(get("de-CH", and), Some("language"));
+        assert_eq!(get("it-IT", and), Some("untagged"));
+        assert_eq!(get("de-AT", in_), Some("im"));
+        assert_eq!(get("de-AT", et_al), Some("u. a."));
+        assert_eq!(get("it-IT", et_al), Some("et al."));
+        assert_eq!(get("fr-FR", et_al), Some("et coll."));
+        assert_eq!(
+            LocaleResolver::new(&LocaleCode("de-AT".into()), &[], &files)
+                .term(in_, TermForm::Long)
+                .and_then(LocalizedTerm::single),
+            Some("in")
+        );
+
+        let resolver = LocaleResolver::new(&LocaleCode("de-AT".into()), &style, &files);
+        assert_eq!(resolver.options().punctuation_in_quote, Some(false));
+
+        // A neutral term of the style wins over a gendered one of a file.
+        let resolver = LocaleResolver::new(&LocaleCode("en-US".into()), &style, &files);
+        let ordinal = Term::Other(OtherTerm::Ordinal);
+        let feminine = Some(GrammarGender::Feminine);
+        assert_eq!(
+            resolver.localize(ordinal, TermForm::Long, false, feminine),
+            Some("th")
+        );
+    }
+
+    #[test]
+    fn term_fallback() {
+        let edition = Term::NumberVariable(NumberVariable::Edition);
+        let editor = Term::NameVariable(NameVariable::Editor);
+        let page = Term::Locator(Locator::Page);
+        let ordinal = Term::Other(OtherTerm::Ordinal);
+        let style = locale(
+            None,
+            r#"<term name="editor"><single>editor</single><multiple>editors</multiple></term>
+<term name="editor" form="verb">edited by</term>
+<term name="page" form="short"><single>p.</single><multiple>pp.</multiple></term>
+<term name="edition" gender="feminine">édition</term>
+<term name="ordinal">e</term>
+<term name="ordinal" gender-form="feminine">re</term>"#,
+        );
+        let resolver = LocaleResolver::from(&style);
+        let get = |term, form, plural| resolver.localize(term, form, plural, None);
+
+        assert_eq!(get(editor, TermForm::VerbShort, false), Some("edited by"));
+        assert_eq!(get(editor, TermForm::Short, true), Some("editors"));
+        assert_eq!(get(editor, TermForm::Symbol, false), Some("editor"));
+        assert_eq!(get(page, TermForm::Symbol, true), Some("pp."));
+        assert_eq!(get(page, TermForm::Long, false), None);
+
+        let gender = resolver.gender(edition);
+        assert_eq!(gender, Some(GrammarGender::Feminine));
+        assert_eq!(resolver.localize(ordinal, TermForm::Long, false, gender), Some("re"));
+        assert_eq!(
+            resolver.localize(
+                ordinal,
+                TermForm::Long,
+                false,
+                Some(GrammarGender::Masculine)
+            ),
+            Some("e")
+        );
+    }
+
+    #[test]
+    fn locale_files() {
+        let files = [
+            LocaleFile::from_xml(include_str!("../tests/locales/locales-en-US.xml"))
+                .unwrap(),
+            LocaleFile::from_xml(include_str!("../tests/locales/locales-zh-CN.xml"))
+                .unwrap(),
+        ];
+
+        let resolver = LocaleResolver::new(&LocaleCode("zh".into()), &[], &files);
+        let zh = Locale::from(files[1].clone());
+        assert_eq!(
+            resolver.date(DateForm::Text),
+            zh.date.iter().find(|d| d.form == Some(DateForm::Text))
+        );
+        assert_eq!(
+            resolver.ordinals().lookup(1, None),
+            zh.ordinals().unwrap().lookup(1, None)
+        );
+
+        // Terms missing in the Chinese locale come from en-US.
+        let en = Locale::from(files[0].clone());
+        let missing = en
+            .terms
+            .iter()
+            .flat_map(|t| &t.terms)
+            .find(|t| zh.term(t.name, t.form).is_none());
+        if let Some(missing) = missing {
+            assert_eq!(resolver.term(missing.name, missing.form), Some(missing));
+        }
+    }
+
+    #[test]
+    fn locators() {
+        let style = [locale(
+            Some("de-DE"),
+            r#"<term name="page" form="short"><single>S.</single><multiple>S.</multiple></term>
+<term name="chapter" form="short"><single>Kap.</single><multiple>Kap.</multiple></term>"#,
+        )];
+        let files =
+            [LocaleFile::from_xml(include_str!("../tests/locales/locales-en-US.xml"))
+                .unwrap()];
+        let resolver = LocaleResolver::new(&LocaleCode("de-DE".into()), &style, &files);
+        let parse = |text| resolver.parse_locator(text);
+
+        assert_eq!(parse("S. 14"), Some((Locator::Page, "14")));
+        assert_eq!(parse("Kap. 3"), Some((Locator::Chapter, "3")));
+        assert_eq!(parse(" p. 12 "), Some((Locator::Page, "12")));
+        assert_eq!(parse("Pages 3-4"), Some((Locator::Page, "3-4")));
+        assert_eq!(parse("chap. 3"), Some((Locator::Chapter, "3")));
+        assert_eq!(parse("§4"), Some((Locator::Section, "4")));
+        assert_eq!(parse("§§ 4–6"), Some((Locator::Section, "4–6")));
+        assert_eq!(parse("¶¶ 2, 5"), Some((Locator::Paragraph, "2, 5")));
+        assert_eq!(parse("vol. 2"), Some((Locator::Volume, "2")));
+        assert_eq!(parse("lines 5"), Some((Locator::Line, "5")));
+        assert_eq!(parse("partial 5"), None);
+        assert_eq!(parse("12"), None);
+        assert_eq!(parse(""), None);
+    }
+}
@@ -0,0 +1,360 @@
+//! Numeric content of variables.
+
+use std::fmt::Write;
+
+use crate::{GrammarGender, NumberForm, OrdinalLookup, PageRangeFormat};
+
+/// Whether a variable value is numeric in the sense of CSL's `is-numeric`
+/// test.
+///
+/// A value is numeric if it consists of numbers that are optionally prefixed
+/// or suffixed with letters, like "D2", "2b", or "L2d", and that may be
+/// separated by commas, hyphens, or ampersands, like "2, 3", "2-4", or
+/// "2 & 4". Values like "second" or "2nd edition" are not numeric.
+pub fn is_numeric(value: &str) -> bool {
+    !value.trim().is_empty()
+        && value
+            .split([',', '&', '-', '–'])
+            .all(|part| is_numeric_part(part.trim()))
+}
+
+/// Whether the value is a single number with optional letter affixes.
+fn is_numeric_part(part: &str) -> bool {
+    let rest = part.trim_start_matches(char::is_alphabetic);
+    let suffix = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    suffix.len() < rest.len() && suffix.chars().all(char::is_alphabetic)
+}
+
+/// A piece of a numeric variable value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum NumberPart<'s> {
+    /// A number without affixes, together with its text as written, like
+    /// "007".
+    Number(i32, &'s str),
+    /// A number with letter affixes like "2b", which is never transformed.
+    Affixed(&'s str),
+    /// A comma, ampersand, or hyphen between numbers with its surrounding
+    /// whitespace.
+    Separator(&'s str),
+}
+
+/// Split a numeric value into numbers and the separators between them.
+/// Returns `None` if the value is not [numeric](is_numeric).
+pub fn parse(value: &str) -> Option<Vec<NumberPart<'_>>> {
+    if !is_numeric(value) {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = value.trim();
+    loop {
+        let end = rest.find([',', '&', '-', '–']).unwrap_or(rest.len());
+        let token = rest[..end].trim_end();
+        parts.push(match token.parse() {
+            Ok(n) => NumberPart::Number(n, token),
+            Err(_) => NumberPart::Affixed(token),
+        });
+
+        let Some(separator) = rest[end..].chars().next() else { break };
+        let after = &rest[end + separator.len_utf8()..];
+        let next = rest.len() - after.trim_start().len();
+        parts.push(NumberPart::Separator(&rest[token.len()..next]));
+        rest = &rest[next..];
+    }
+
+    Some(parts)
+}
+
+/// Render a number variable in the form of a `cs:number` element.
+///
+/// Each number is transformed on its own, so "2, 3" can become "2nd, 3rd" or
+/// "ii, iii". The separators are kept as they are. Values that are not
+/// numeric and numbers with affixes are returned unchanged. Ordinals agree
+/// with `gender`, which is usually the gender of the term the number belongs
+/// to.
+pub fn format(
+    value: &str,
+    form: NumberForm,
+    ordinals: &OrdinalLookup,
+    gender: Option<GrammarGender>,
+) -> String {
+    let Some(parts) = parse(value) else { return value.to_string() };
+    let ordinal = |n: i32| {
+        let suffix = ordinals.lookup(n, gender).unwrap_or_default();
+        format!("{n}{suffix}")
+    };
+
+    let mut out = String::new();
+    for part in parts {
+        match part {
+            // Numbers are only reformatted for the other forms, so "007" stays
+            // as it is.
+            NumberPart::Number(n, text) => out.push_str(&match form {
+                NumberForm::Numeric => text.to_string(),
+                NumberForm::Ordinal => ordinal(n),
+                NumberForm::LongOrdinal => match ordinals.lookup_long(n) {
+                    Some(long) => long.to_string(),
+                    None => ordinal(n),
+                },
+                NumberForm::Roman => to_roman(n).unwrap_or_else(|| n.to_string()),
+            }),
+            NumberPart::Affixed(text) | NumberPart::Separator(text) => out.push_str(text),
+        }
+    }
+    out
+}
+
+/// A piece of a page or locator value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PagePart<'s> {
+    /// A range of pages that are numbered the same way, like "12-15",
+    /// "iv-ix", or "S12-S19", with its first and last page.
+    Range(&'s str, &'s str),
+    /// Anything else, like single pages, separators, and ranges that mix
+    /// roman and arabic numbers.
+    Other(&'s str),
+}
+
+/// Split a page value like "iv-ix, 12-15 & 17" into its page ranges and the
+/// text between them.
+///
+/// Commas and ampersands separate the ranges. A range consists of two pages
+/// that are joined by hyphens or an en dash. The pages must either both
+/// contain arabic numbers, optionally with letters like in "S12", or both be
+/// roman numerals of the same case.
+pub fn parse_pages(value: &str) -> Vec<PagePart<'_>> {
+    let mut parts = Vec::new();
+    let mut other = 0;
+    let mut offset = 0;
+    for token in value.split([',', '&']) {
+        let start = offset + (token.len() - token.trim_start().len());
+        let trimmed = token.trim();
+        if let Some((first, last)) = page_range(trimmed) {
+            if other < start {
+                parts.push(PagePart::Other(&value[other..start]));
+            }
+            parts.push(PagePart::Range(first, last));
+            other = start + trimmed.len();
+        }
+
+        // Skip the token and the separator after it, which is one byte long.
+        offset += token.len() + 1;
+    }
+
+    if other < value.len() {
+        parts.push(PagePart::Other(&value[other..]));
+    }
+
+    parts
+}
+
+/// Reformat the page ranges of a page or locator value.
+///
+/// Ranges of arabic numbers are shortened or expanded according to the page
+/// range format, while roman numeral ranges are always written in full. All
+/// ranges are joined with the delimiter. Everything else stays as it is.
+///
+/// ```
+/// use citationberg::PageRangeFormat;
+/// use citationberg::number::format_pages;
+///
+/// let format = Some(PageRangeFormat::Minimal);
+/// assert_eq!(format_pages("iv-ix, 321-328 & 17", format, "–"), "iv–ix, 321–8 & 17");
+/// assert_eq!(format_pages("S12--S19", format, "–"), "S12–9");
+/// ```
+pub fn format_pages(
+    value: &str,
+    format: Option<PageRangeFormat>,
+    delimiter: &str,
+) -> String {
+    let mut out = String::new();
+    for part in parse_pages(value) {
+        match part {
+            PagePart::Range(first, last) => match format {
+                // Writing to a string cannot fail.
+                Some(format) if !is_roman(first) => {
+                    let _ = format.format(&mut out, first, last, Some(delimiter));
+                }
+                _ => {
+                    let _ = write!(out, "{first}{delimiter}{last}");
+                }
+            },
+            PagePart::Other(text) => out.push_str(text),
+        }
+    }
+    out
+}
+
+/// The first page of a page value, which is the value of the `page-first`
+/// variable. Returns `None` if the value starts with a separator.
+pub fn page_first(value: &str) -> Option<&str> {
+    let first = value.split([',', '&', '-', '–']).next()?.trim();
+    (!first.is_empty()).then_some(first)
+}
+
+/// Split a range of pages into its first and last page.
+fn page_range(token: &str) -> Option<(&str, &str)> {
+    let (first, last) = token.split_once(['-', '–'])?;
+    let first = first.trim_end();
+    let last = last.trim_start_matches(['-', '–']).trim_start();
+    if last.contains(['-', '–']) {
+        return None;
+    }
+
+    let arabic = |page: &str| {
+        page.contains(|c: char| c.is_ascii_digit())
+            && page.chars().all(char::is_alphanumeric)
+    };
+    let same_case = |a: &str, b: &str| {
+        let upper = |s: &str| s.chars().all(|c| c.is_ascii_uppercase());
+        upper(a) == upper(b)
+    };
+
+    let is_range = (arabic(first) && arabic(last))
+        || (is_roman(first) && is_roman(last) && same_case(first, last));
+    is_range.then_some((first, last))
+}
+
+/// Whether a page is a valid lowercase or uppercase roman numeral.
+fn is_roman(page: &str) -> bool {
+    let lower = page.to_ascii_lowercase();
+    if lower != page && page.to_ascii_uppercase() != page {
+        return false;
+    }
+
+    let value = |c| match c {
+        'i' => Some(1),
+        'v' => Some(5),
+        'x' => Some(10),
+        'l' => Some(50),
+        'c' => Some(100),
+        'd' => Some(500),
+        'm' => Some(1000),
+        _ => None,
+    };
+
+    let Some(values) = lower.chars().map(value).collect::<Option<Vec<i32>>>() else {
+        return false;
+    };
+
+    let mut n = 0;
+    for (k, &v) in values.iter().enumerate() {
+        match values.get(k + 1) {
+            Some(&next) if next > v => n -= v,
+            _ => n += v,
+        }
+    }
+
+    // Only numerals in canonical form, like "iv" and not "iiii".
+    to_roman(n).is_some_and(|roman| roman == lower)
+}
+
+/// Write a number between 1 and 3999 as a lowercase roman numeral.
+fn to_roman(mut n: i32) -> Option<String> {
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+
+    if !(1..4000).contains(&n) {
+        return None;
+    }
+
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numeric() {
+        for value in
+            ["2", " 12 ", "D2", "2b", "L2d", "2nd", "2, 3", "2-4", "2 & 4", "1–3"]
+        {
+            assert!(is_numeric(value), "{value:?} should be numeric");
+        }
+
+        for value in ["", "second", "2nd edition", "2,", "-4", "2 4"] {
+            assert!(!is_numeric(value), "{value:?} should not be numeric");
+        }
+    }
+
+    #[test]
+    fn formatting() {
+        let file = crate::LocaleFile::from_xml(include_str!(
+            "../tests/locales/locales-en-US.xml"
+        ))
+        .unwrap();
+        let locale = crate::Locale::from(file);
+        let ordinals = locale.ordinals().unwrap();
+        let format = |value, form| format(value, form, &ordinals, None);
+
+        assert_eq!(format("2, 3", NumberForm::Ordinal), "2nd, 3rd");
+        assert_eq!(format("1 & 12-13", NumberForm::Ordinal), "1st & 12th-13th");
+        assert_eq!(format("2, 3", NumberForm::LongOrdinal), "second, third");
+        assert_eq!(format("4–9", NumberForm::Roman), "iv–ix");
+        assert_eq!(format("2b, 3", NumberForm::Roman), "2b, iii");
+        assert_eq!(format("2nd ed", NumberForm::Roman), "2nd ed");
+        assert_eq!(format("007, 08", NumberForm::Numeric), "007, 08");
+        assert_eq!(format("007", NumberForm::Ordinal), "7th");
+        assert_eq!(
+            parse("2b ,3"),
+            Some(vec![
+                NumberPart::Affixed("2b"),
+                NumberPart::Separator(" ,"),
+                NumberPart::Number(3, "3")
+            ])
+        );
+    }
+
+    #[test]
+    fn pages() {
+        assert_eq!(
+            parse_pages("iv-ix, 12--15 & 17"),
+            [
+                PagePart::Range("iv", "ix"),
+                PagePart::Other(", "),
+                PagePart::Range("12", "15"),
+                PagePart::Other(" & 17"),
+            ]
+        );
+        assert_eq!(parse_pages("iv-9, A-C"), [PagePart::Other("iv-9, A-C")]);
+        assert_eq!(parse_pages("IV-ix"), [PagePart::Other("IV-ix")]);
+        assert_eq!(
+            parse_pages(" XL – L "),
+            [PagePart::Other(" "), PagePart::Range("XL", "L"), PagePart::Other(" "),]
+        );
+
+        let format = |value, format| format_pages(value, format, "–");
+        let chicago = Some(PageRangeFormat::Chicago16);
+        assert_eq!(format("321-328, 12-15 & 17", chicago), "321–28, 12–15 & 17");
+        assert_eq!(format("S12-S19", Some(PageRangeFormat::Expanded)), "S12–S19");
+        assert_eq!(format("xii-xviii, 1-1", chicago), "xii–xviii, 1–1");
+        assert_eq!(format("101-108", None), "101–108");
+        assert_eq!(format("Introduction", chicago), "Introduction");
+
+        assert_eq!(page_first("iv-ix, 12"), Some("iv"));
+        assert_eq!(page_first(" 12 & 17"), Some("12"));
+        assert_eq!(page_first("-12"), None);
+        assert!(is_roman("MCMXC") && !is_roman("iiii") && !is_roman("Iv"));
+    }
+}
@@ -0,0 +1,205 @@
+//! Text case transformations.
+
+use crate::TextCase;
+
+/// Words that stay lowercase in English title case unless they start or end
+/// the text or follow a colon.
+const STOP_WORDS: [&str; 26] = [
+    "a", "an", "and", "as", "at", "but", "by", "down", "for", "from", "in", "into",
+    "nor", "of", "on", "onto", "or", "over", "so", "the", "till", "to", "up", "via",
+    "with", "yet",
+];
+
+/// Change the case of a text.
+///
+/// Title case follows the English rules of CSL: Lowercase words are
+/// capitalized, stop words are lowercased unless they are the first or last
+/// word or follow a colon, and the parts of hyphenated compounds are treated
+/// as separate words. Text that is all uppercase is lowercased before. Check
+/// [`is_english`] before applying title or sentence case.
+///
+/// ```
+/// use citationberg::TextCase;
+/// use citationberg::render::change_case;
+///
+/// let title = change_case("the lord of the rings: the return of the king", TextCase::TitleCase);
+/// assert_eq!(title, "The Lord of the Rings: The Return of the King");
+/// ```
+pub fn change_case(text: &str, case: TextCase) -> String {
+    let mut text = text.to_string();
+    change_case_pieces(&mut [(&mut text, false)], case);
+    text
+}
+
+/// Whether a language tag like `en-US` refers to English.
+pub fn is_english(language: &str) -> bool {
+    let language = language.trim().to_ascii_lowercase();
+    language == "english"
+        || language
+            .strip_prefix("en")
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', '_']))
+}
+
+/// Change the case of text that is split into pieces, like the text nodes of
+/// formatted output. Words can span multiple pieces. Pieces that are marked
+/// as protected keep their case but still count as words.
+pub(super) fn change_case_pieces(pieces: &mut [(&mut String, bool)], case: TextCase) {
+    let chars: Vec<(char, bool)> = pieces
+        .iter()
+        .flat_map(|(text, protected)| text.chars().map(|c| (c, *protected)))
+        .collect();
+    let mut out: Vec<Case> = vec![Case::Keep; chars.len()];
+
+    let words = words(&chars);
+    let uppercase = chars
+        .iter()
+        .filter(|(c, protected)| !protected && c.is_alphabetic())
+        .all(|(c, _)| !c.is_lowercase());
+
+    for (k, word) in words.iter().enumerate() {
+        let text = &chars[word.clone()];
+        if text.iter().any(|&(_, protected)| protected) {
+            continue;
+        }
+
+        let lower = text.iter().all(|(c, _)| !c.is_uppercase());
+        let first = k == 0;
+        let capitalize = match case {
+            TextCase::Lowercase => {
+                out[word.clone()].fill(Case::Lower);
+                continue;
+            }
+            TextCase::Uppercase => {
+                out[word.clone()].fill(Case::Upper);
+                continue;
+            }
+            TextCase::CapitalizeFirst => first && lower,
+            TextCase::CapitalizeAll => lower,
+            TextCase::SentenceCase => {
+                if uppercase {
+                    out[word.clone()].fill(Case::Lower);
+                }
+                first && (uppercase || lower)
+            }
+            TextCase::TitleCase => {
+                let before = if first { 0 } else { words[k - 1].end };
+                let after_colon =
+                    chars[before..word.start].iter().any(|&(c, _)| c == ':');
+                let last = k + 1 == words.len();
+                let string: String = text.iter().map(|(c, _)| c).collect();
+                if STOP_WORDS.contains(&string.to_lowercase().as_str())
+                    && !(first || last || after_colon)
+                {
+                    out[word.clone()].fill(Case::Lower);
+                    continue;
+                }
+
+                if uppercase {
+                    out[word.clone()].fill(Case::Lower);
+                }
+                uppercase || lower || first || last || after_colon
+            }
+        };
+
+        if capitalize {
+            out[word.start] = Case::Upper;
+        }
+    }
+
+    let mut chars = chars.into_iter().zip(out);
+    for (text, _) in pieces.iter_mut() {
+        let len = text.chars().count();
+        let mut changed = String::with_capacity(text.len());
+        for ((c, _), case) in chars.by_ref().take(len) {
+            match case {
+                Case::Keep => changed.push(c),
+                Case::Lower => changed.extend(c.to_lowercase()),
+                Case::Upper => changed.extend(c.to_uppercase()),
+            }
+        }
+        **text = changed;
+    }
+}
+
+/// How to change a character.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Case {
+    Keep,
+    Lower,
+    Upper,
+}
+
+/// The character ranges of the words in the text. Apostrophes within words
+/// belong to them, hyphens separate them.
+fn words(chars: &[(char, bool)]) -> Vec<std::ops::Range<usize>> {
+    let is_word = |i: usize| {
+        let c = chars[i].0;
+        c.is_alphanumeric()
+            || (matches!(c, '\'' | '’')
+                && i > 0
+                && chars[i - 1].0.is_alphanumeric()
+                && chars.get(i + 1).is_some_and(|(c, _)| c.is_alphanumeric()))
+    };
+
+    let mut words = Vec::new();
+    let mut start = None;
+    for i in 0..chars.len() {
+        match (start, is_word(i)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        words.push(s..chars.len());
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cases() {
+        let title = |text| change_case(text, TextCase::TitleCase);
+        assert_eq!(title("a tale of two cities"), "A Tale of Two Cities");
+        assert_eq!(title("THE WAR OF THE WORLDS"), "The War of the Worlds");
+        assert_eq!(
+            title("self-evident truths in the iPhone era"),
+            "Self-Evident Truths in the iPhone Era"
+        );
+        assert_eq!(title("war and peace: a study of"), "War and Peace: A Study Of");
+        assert_eq!(title("o’brien’s über-guide"), "O’brien’s Über-Guide");
+
+        let sentence = |text| change_case(text, TextCase::SentenceCase);
+        assert_eq!(sentence("THE WAR OF THE WORLDS"), "The war of the worlds");
+        assert_eq!(sentence("the War of the Worlds"), "The War of the Worlds");
+
+        assert_eq!(change_case("straße", TextCase::Uppercase), "STRASSE");
+        assert_eq!(change_case("élan vital", TextCase::CapitalizeAll), "Élan Vital");
+        assert_eq!(change_case("élan vital", TextCase::CapitalizeFirst), "Élan vital");
+        assert_eq!(
+            change_case("iPhone sales", TextCase::CapitalizeFirst),
+            "iPhone sales"
+        );
+        assert_eq!(change_case("iPhone sales", TextCase::CapitalizeAll), "iPhone Sales");
+
+        let mut protected = String::from("the iPhone");
+        let mut rest = String::from(" and the ipad");
+        change_case_pieces(
+            &mut [(&mut protected, true), (&mut rest, false)],
+            TextCase::TitleCase,
+        );
+        assert_eq!(protected + &rest, "the iPhone and the Ipad");
+
+        assert!(is_english("en-US"));
+        assert!(is_english("English"));
+        assert!(!is_english("de-DE"));
+        assert!(!is_english("eng-x"));
+    }
+}
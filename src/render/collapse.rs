@@ -0,0 +1,364 @@
+//! Grouping and collapsing of the cites in a citation.
+
+use super::{Cite, Node, Output, Processor, RenderError, Renderer, disambiguate, join};
+use crate::json::{self, FixedDateRange, Value};
+use crate::{Citation, Collapse, InheritableNameOptions};
+
+/// A rendered cite of a citation together with what is needed to group and
+/// collapse it with the other cites.
+///
+/// The fields that enable collapsing should only be set if the cite has no
+/// locator or affixes: A cite like "Doe 2000a, p. 5" cannot be shortened to
+/// its year suffix.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct ClusterCite {
+    /// The complete cite.
+    pub output: Vec<Node>,
+    /// The cite without its names. It follows the first cite of a group
+    /// whose years are collapsed.
+    pub without_names: Vec<Node>,
+    /// The rendered names. Cites with the same names form a group.
+    pub names: Option<String>,
+    /// The citation number, if the cite can be part of a number range.
+    pub number: Option<usize>,
+    /// The rendered year, if the cite can be shortened to its year suffix.
+    pub year: Option<String>,
+    /// The index of the year suffix, where 0 is "a".
+    pub year_suffix: Option<usize>,
+}
+
+/// Join the cites of a citation, grouping and collapsing them according to
+/// the `collapse` and delimiter options of the citation.
+///
+/// Cites with the same names are moved next to the first of them if the
+/// citation collapses by year or has a `cite-group-delimiter`. Consecutive
+/// citation numbers collapse into ranges like "1–3, 5" and the years of a
+/// group into lists like "Doe 2000a, c". Ranges are joined with the
+/// `range_delimiter`, which is the `citation-range-delimiter` term of the
+/// locale. The layout delimiter separates the groups, except after collapsed
+/// ones, which use the `after-collapse-delimiter`.
+///
+/// ```
+/// use citationberg::render::{ClusterCite, Node, Output, collapse};
+/// use citationberg::{Citation, Collapse, Layout};
+///
+/// let layout = Layout::new(vec![], Default::default(), None, Some(", ".into()));
+/// let mut citation = Citation::with_layout(layout);
+/// citation.collapse = Some(Collapse::CitationNumber);
+///
+/// let cites = [1, 2, 3, 5].map(|n| ClusterCite {
+///     output: vec![Node::Text(n.to_string())],
+///     number: Some(n),
+///     ..ClusterCite::default()
+/// });
+///
+/// let output = Output(collapse(&citation, cites.to_vec(), "–"));
+/// assert_eq!(output.to_plain(), "1–3, 5");
+/// ```
+pub fn collapse(
+    citation: &Citation,
+    cites: Vec<ClusterCite>,
+    range_delimiter: &str,
+) -> Vec<Node> {
+    let groups = match citation.collapse {
+        Some(Collapse::CitationNumber) => number_ranges(cites, range_delimiter),
+        None if citation.cite_group_delimiter.is_none() => {
+            cites.into_iter().map(|cite| (cite.output, false)).collect()
+        }
+        _ => name_groups(citation, cites, range_delimiter),
+    };
+
+    let delimiter = citation.layout.delimiter.as_deref().unwrap_or_default();
+    let mut nodes = Vec::new();
+    let mut collapsed = false;
+    for (group, is_collapsed) in groups {
+        if group.iter().all(Node::is_empty) {
+            continue;
+        }
+
+        if !nodes.is_empty() {
+            let delimiter = if collapsed {
+                citation.get_after_collapse_delimiter()
+            } else {
+                delimiter
+            };
+            nodes.push(Node::Text(delimiter.to_string()));
+        }
+
+        nodes.extend(group);
+        collapsed = is_collapsed;
+    }
+    nodes
+}
+
+impl Processor<'_> {
+    /// Render a cite of a citation cluster with its affixes. Returns `None`
+    /// if the cite is empty.
+    pub(super) fn cluster_cite(
+        &self,
+        cite: &Cite,
+        citation_item: &json::CitationItem,
+        name_options: &InheritableNameOptions,
+    ) -> Result<Option<ClusterCite>, RenderError> {
+        let citation = &self.style.citation;
+        let mut renderer = Renderer::new(self, cite, name_options);
+        renderer.suppress_author = citation_item.suppress_author;
+        let output = renderer.render_layout(&citation.layout)?;
+        if output.iter().all(Node::is_empty) {
+            return Ok(None);
+        }
+
+        let names = renderer
+            .first_names
+            .take()
+            .filter(|_| !citation_item.suppress_author)
+            .map(|names| Output(names).to_plain());
+
+        let mut without_names = Vec::new();
+        if names.is_some()
+            && (citation.collapse.is_some() || citation.cite_group_delimiter.is_some())
+        {
+            let mut renderer = Renderer::new(self, cite, name_options);
+            renderer.suppress_author = true;
+            without_names = renderer.render_layout(&citation.layout)?;
+        }
+
+        let affixed = |mut nodes: Vec<Node>| {
+            if let Some(prefix) = &citation_item.prefix {
+                nodes.insert(0, Node::Text(prefix.clone()));
+            }
+            if let Some(suffix) = &citation_item.suffix {
+                nodes.push(Node::Text(suffix.clone()));
+            }
+            nodes
+        };
+
+        let collapsible = cite.locator.is_none()
+            && citation_item.prefix.is_none()
+            && citation_item.suffix.is_none();
+        let year = match cite.item.0.get("issued") {
+            Some(Value::Date(date)) if collapsible => {
+                FixedDateRange::try_from(date.clone())
+                    .ok()
+                    .map(|range| range.start.year.to_string())
+            }
+            _ => None,
+        };
+
+        Ok(Some(ClusterCite {
+            output: affixed(output),
+            without_names: affixed(without_names),
+            names,
+            number: collapsible.then_some(cite.number),
+            year,
+            year_suffix: cite.disambiguation.year_suffix.filter(|_| collapsible),
+        }))
+    }
+}
+
+/// Collapse runs of three or more consecutive citation numbers into ranges.
+fn number_ranges(
+    cites: Vec<ClusterCite>,
+    range_delimiter: &str,
+) -> Vec<(Vec<Node>, bool)> {
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < cites.len() {
+        let mut j = i;
+        while let Some(next) = cites.get(j + 1)
+            && let (Some(a), Some(b)) = (cites[j].number, next.number)
+            && b == a + 1
+        {
+            j += 1;
+        }
+
+        if j - i >= 2 {
+            let mut nodes = cites[i].output.clone();
+            nodes.push(Node::Text(range_delimiter.to_string()));
+            nodes.extend(cites[j].output.iter().cloned());
+            groups.push((nodes, true));
+        } else {
+            groups.extend(cites[i..=j].iter().map(|cite| (cite.output.clone(), false)));
+        }
+
+        i = j + 1;
+    }
+    groups
+}
+
+/// Group cites with the same names and collapse the years of each group.
+fn name_groups(
+    citation: &Citation,
+    cites: Vec<ClusterCite>,
+    range_delimiter: &str,
+) -> Vec<(Vec<Node>, bool)> {
+    let mut grouped: Vec<Vec<ClusterCite>> = Vec::new();
+    for cite in cites {
+        let group = cite.names.as_ref().and_then(|names| {
+            grouped
+                .iter_mut()
+                .find(|group| group[0].names.as_ref() == Some(names))
+        });
+
+        match group {
+            Some(group) => group.push(cite),
+            None => grouped.push(vec![cite]),
+        }
+    }
+
+    let cite_group_delimiter = citation
+        .cite_group_delimiter
+        .as_deref()
+        .unwrap_or(Citation::DEFAULT_CITE_GROUP_DELIMITER);
+
+    grouped
+        .into_iter()
+        .map(|group| {
+            if group.len() == 1 {
+                let cite = group.into_iter().next().unwrap();
+                return (cite.output, false);
+            }
+
+            let parts = match citation.collapse {
+                Some(Collapse::YearSuffix) => year_suffixes(citation, group, None),
+                Some(Collapse::YearSuffixRanged) => {
+                    year_suffixes(citation, group, Some(range_delimiter))
+                }
+                Some(Collapse::Year) => group
+                    .into_iter()
+                    .enumerate()
+                    .map(
+                        |(k, cite)| if k == 0 { cite.output } else { cite.without_names },
+                    )
+                    .collect(),
+                _ => group.into_iter().map(|cite| cite.output).collect(),
+            };
+
+            (join(parts, Some(cite_group_delimiter)), true)
+        })
+        .collect()
+}
+
+/// Collapse the cites of a name group that share a year to their year
+/// suffixes, like "2000a, c" or, if they are ranged, "2000a–c".
+fn year_suffixes(
+    citation: &Citation,
+    group: Vec<ClusterCite>,
+    ranged: Option<&str>,
+) -> Vec<Vec<Node>> {
+    let delimiter = citation.get_year_suffix_delimiter();
+    let suffix = |index| Node::Text(disambiguate::year_suffix(index));
+
+    let mut parts: Vec<Vec<Node>> = Vec::new();
+    let mut run: Vec<usize> = Vec::new();
+    let mut previous: Option<&ClusterCite> = None;
+    for (k, cite) in group.iter().enumerate() {
+        let continues = previous.is_some_and(|previous| {
+            previous.year.is_some()
+                && previous.year == cite.year
+                && previous.year_suffix.is_some()
+        });
+
+        match cite.year_suffix {
+            Some(index) if continues => run.push(index),
+            _ => {
+                finish_run(parts.last_mut(), &mut run, delimiter, ranged, &suffix);
+                parts.push(if k == 0 {
+                    cite.output.clone()
+                } else {
+                    cite.without_names.clone()
+                });
+                run.extend(cite.year_suffix);
+            }
+        }
+
+        previous = Some(cite);
+    }
+
+    finish_run(parts.last_mut(), &mut run, delimiter, ranged, &suffix);
+    parts
+}
+
+/// Append the year suffixes of a run to the cite that starts it. The suffix
+/// of that cite is already part of its output.
+fn finish_run(
+    part: Option<&mut Vec<Node>>,
+    run: &mut Vec<usize>,
+    delimiter: &str,
+    ranged: Option<&str>,
+    suffix: &impl Fn(usize) -> Node,
+) {
+    let run = std::mem::take(run);
+    let Some(part) = part else { return };
+
+    let mut segments: Vec<Vec<usize>> = Vec::new();
+    for index in run {
+        match segments.last_mut() {
+            Some(segment)
+                if ranged.is_some()
+                    && segment.last().is_some_and(|&l| l + 1 == index) =>
+            {
+                segment.push(index)
+            }
+            _ => segments.push(vec![index]),
+        }
+    }
+
+    for (k, segment) in segments.iter().enumerate() {
+        if k > 0 {
+            part.push(Node::Text(delimiter.to_string()));
+            part.push(suffix(segment[0]));
+        }
+
+        if let Some(range_delimiter) = ranged
+            && segment.len() >= 3
+        {
+            part.push(Node::Text(range_delimiter.to_string()));
+            part.push(suffix(segment[segment.len() - 1]));
+        } else {
+            for &index in &segment[1..] {
+                part.push(Node::Text(delimiter.to_string()));
+                part.push(suffix(index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Layout;
+
+    #[test]
+    fn year_suffix_ranges() {
+        let layout = Layout::new(vec![], Default::default(), None, Some("; ".into()));
+        let mut citation = Citation::with_layout(layout);
+        citation.collapse = Some(Collapse::YearSuffixRanged);
+        citation.year_suffix_delimiter = Some(",".into());
+
+        let cite = |names: &str, year: &str, suffix: Option<usize>| {
+            let letter = suffix.map(disambiguate::year_suffix).unwrap_or_default();
+            ClusterCite {
+                output: vec![Node::Text(format!("{names} {year}{letter}"))],
+                without_names: vec![Node::Text(format!("{year}{letter}"))],
+                names: Some(names.into()),
+                year: Some(year.into()),
+                year_suffix: suffix,
+                ..ClusterCite::default()
+            }
+        };
+
+        let cites = vec![
+            cite("Doe", "2000", Some(0)),
+            cite("Doe", "2000", Some(1)),
+            cite("Roe", "1999", None),
+            cite("Doe", "2000", Some(2)),
+            cite("Doe", "2000", Some(4)),
+            cite("Doe", "2001", Some(0)),
+            cite("Doe", "2001", Some(1)),
+        ];
+
+        let output = Output(collapse(&citation, cites, "–"));
+        assert_eq!(output.to_plain(), "Doe 2000a–c,e, 2001a,b; Roe 1999");
+    }
+}
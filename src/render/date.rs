@@ -0,0 +1,269 @@
+//! Rendering of `cs:date` elements.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use super::{Attributes, Node, Renderer, disambiguate, join, sort};
+use crate::json::{FixedDate, FixedDateRange};
+use crate::taxonomy::{OtherTerm, Season, Term, Variable};
+use crate::{
+    Date, DateDayForm, DateMonthForm, DatePart, DatePartName, DateStrongAnyForm,
+    LongShortForm, TermForm, ToAffixes, ToFormatting,
+};
+
+impl Renderer<'_> {
+    pub(super) fn render_date(&mut self, date: &Date) -> Vec<Node> {
+        let Some(variable) = date.variable else { return Vec::new() };
+        let value = self.date(variable);
+        self.track(Variable::Date(variable), value.is_some());
+        let Some(value) = value else { return Vec::new() };
+
+        // Dates in sort keys sort chronologically by the parts they render.
+        if self.sort_key.is_some()
+            && let Ok(range) = FixedDateRange::try_from(value.clone())
+        {
+            let (parts, _) = self.date_parts(date);
+            let has = |name| parts.iter().any(|p| p.name == name);
+            let key =
+                sort::date_key(range, has(DatePartName::Month), has(DatePartName::Day));
+            return vec![Node::Text(key)];
+        }
+
+        let nodes = match FixedDateRange::try_from(value.clone()) {
+            Ok(range) => self.render_range(date, range),
+            Err(()) => Vec::new(),
+        };
+
+        let mut nodes = match value.literal() {
+            Some(literal) if nodes.is_empty() => vec![Node::Text(literal.to_string())],
+            _ => nodes,
+        };
+
+        // Styles that do not render the year suffix get it after the first
+        // date.
+        if self.implicit_year_suffix && !nodes.is_empty() {
+            self.implicit_year_suffix = false;
+            let index = self.cite.disambiguation.year_suffix.unwrap_or_default();
+            nodes.push(Node::Text(disambiguate::year_suffix(index)));
+        }
+
+        self.decorate(
+            nodes,
+            Attributes {
+                formatting: date.to_formatting(),
+                affixes: date.to_affixes(),
+                display: date.display,
+                text_case: date.text_case,
+                ..Attributes::default()
+            },
+        )
+    }
+
+    /// Render a date or date range. The parts that both ends of a range share
+    /// are only rendered once, e.g. "May 3–7, 2020".
+    fn render_range(&self, date: &Date, range: FixedDateRange) -> Vec<Node> {
+        let (parts, delimiter) = self.date_parts(date);
+        let start = self.render_parts(&parts, range.start);
+        let all = 0..parts.len();
+        let whole = |range| join_parts(&parts, &start, range, delimiter, false, false);
+
+        let Some(end) = range.end.filter(|&end| end != range.start) else {
+            return whole(all);
+        };
+
+        let greatest = if range.start.year != end.year {
+            DatePartName::Year
+        } else if range.start.month != end.month || range.start.season != end.season {
+            DatePartName::Month
+        } else {
+            DatePartName::Day
+        };
+
+        let differs = |name: DatePartName| match greatest {
+            DatePartName::Year => true,
+            DatePartName::Month => name != DatePartName::Year,
+            DatePartName::Day => name == DatePartName::Day,
+        };
+
+        // Collapsing only works if the differing parts are next to each other.
+        let ranged: Vec<usize> = parts
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| differs(p.name))
+            .map(|(i, _)| i)
+            .collect();
+        let span = match (ranged.first(), ranged.last()) {
+            (Some(&a), Some(&b)) if b + 1 - a == ranged.len() => a..b + 1,
+            (Some(_), Some(_)) => all,
+            _ => return whole(all),
+        };
+
+        let range_delimiter = parts
+            .iter()
+            .find(|p| p.name == greatest)
+            .and_then(|p| p.range_delimiter.as_deref())
+            .unwrap_or(DatePart::DEFAULT_DELIMITER);
+
+        let end = self.render_parts(&parts, end);
+        let mut middle = join_parts(&parts, &start, span.clone(), delimiter, false, true);
+        middle.push(Node::Text(range_delimiter.to_string()));
+        middle.extend(join_parts(&parts, &end, span.clone(), delimiter, true, false));
+
+        join(vec![whole(0..span.start), middle, whole(span.end..parts.len())], delimiter)
+    }
+
+    /// The parts of a date and the delimiter between them.
+    ///
+    /// Localized dates use the parts of the locale, filtered by the
+    /// `date-parts` attribute and overridden by the parts of the style.
+    fn date_parts<'d>(&self, date: &'d Date) -> (Vec<Cow<'d, DatePart>>, Option<&'d str>)
+    where
+        Self: 'd,
+    {
+        let Some(localized) = date.form.and_then(|form| self.locale().date(form)) else {
+            let parts = date.date_part.iter().map(Cow::Borrowed).collect();
+            return (parts, date.delimiter.as_deref());
+        };
+
+        let filter = date.parts.unwrap_or_default();
+        let parts = localized
+            .date_part
+            .iter()
+            .filter(|p| match p.name {
+                DatePartName::Day => filter.has_day(),
+                DatePartName::Month => filter.has_month(),
+                DatePartName::Year => true,
+            })
+            .map(|p| match date.date_part.iter().find(|o| o.name == p.name) {
+                Some(other) => Cow::Owned(p.apply(other)),
+                None => Cow::Borrowed(p),
+            })
+            .collect();
+
+        (parts, localized.delimiter.as_deref())
+    }
+
+    /// Render each part of a date without its affixes. Parts the date does
+    /// not have are `None`.
+    fn render_parts(
+        &self,
+        parts: &[Cow<'_, DatePart>],
+        value: FixedDate,
+    ) -> Vec<Option<Vec<Node>>> {
+        parts
+            .iter()
+            .map(|part| {
+                let text = match part.form() {
+                    DateStrongAnyForm::Year(form) => Some(self.year(value.year, form)),
+                    DateStrongAnyForm::Month(form) => self.month(value, form),
+                    DateStrongAnyForm::Day(form) => value
+                        .day
+                        .zip(value.month)
+                        .and_then(|(d, m)| Some(self.day(d.checked_add(1)?, m, form))),
+                }?;
+
+                Some(self.decorate(
+                    vec![Node::Text(text)],
+                    Attributes {
+                        formatting: part.to_formatting(),
+                        strip_periods: part.strip_periods,
+                        text_case: part.text_case,
+                        ..Attributes::default()
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    fn year(&self, year: i16, form: LongShortForm) -> String {
+        let era = |term: OtherTerm| {
+            self.locale()
+                .localize(term.into(), TermForm::Long, false, None)
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        match form {
+            _ if year < 0 => format!("{}{}", -(year as i32), era(OtherTerm::Bc)),
+            _ if year > 0 && year < 1000 => format!("{year}{}", era(OtherTerm::Ad)),
+            LongShortForm::Long => year.to_string(),
+            LongShortForm::Short => format!("{:02}", year % 100),
+        }
+    }
+
+    fn month(&self, value: FixedDate, form: DateMonthForm) -> Option<String> {
+        let Some(month) = value.month else { return self.season(value.season?) };
+
+        let term_form = match form {
+            DateMonthForm::Long => TermForm::Long,
+            DateMonthForm::Short => TermForm::Short,
+            DateMonthForm::Numeric => return Some(month.checked_add(1)?.to_string()),
+            DateMonthForm::NumericLeadingZeros => {
+                return Some(format!("{:02}", month.checked_add(1)?));
+            }
+        };
+
+        let term = Term::Other(OtherTerm::month(month)?);
+        self.locale()
+            .localize(term, term_form, false, None)
+            .map(ToString::to_string)
+    }
+
+    fn season(&self, season: Season) -> Option<String> {
+        let term = Term::Other(OtherTerm::season(season));
+        self.locale()
+            .localize(term, TermForm::Long, false, None)
+            .map(ToString::to_string)
+    }
+
+    /// Render a day of the zero-based month. Ordinals agree with the gender of
+    /// the month term.
+    fn day(&self, day: u8, month: u8, form: DateDayForm) -> String {
+        let limit = self.locale().options().limit_day_ordinals_to_day_1.unwrap_or(false);
+        match form {
+            DateDayForm::Ordinal if !limit || day == 1 => {
+                let gender = OtherTerm::month(month)
+                    .and_then(|term| self.locale().gender(Term::Other(term)));
+                let suffix = self
+                    .locale()
+                    .ordinals()
+                    .lookup(day.into(), gender)
+                    .unwrap_or_default();
+                format!("{day}{suffix}")
+            }
+            DateDayForm::NumericLeadingZeros => format!("{day:02}"),
+            DateDayForm::Numeric | DateDayForm::Ordinal => day.to_string(),
+        }
+    }
+}
+
+/// Join the rendered date parts in `range` with their affixes. The prefix of
+/// the first and the suffix of the last part can be left out to attach a
+/// range delimiter.
+fn join_parts(
+    parts: &[Cow<'_, DatePart>],
+    rendered: &[Option<Vec<Node>>],
+    range: Range<usize>,
+    delimiter: Option<&str>,
+    trim_prefix: bool,
+    trim_suffix: bool,
+) -> Vec<Node> {
+    let present: Vec<usize> = range.filter(|&i| rendered[i].is_some()).collect();
+    let mut joined = Vec::with_capacity(present.len());
+    for (k, &i) in present.iter().enumerate() {
+        let affixes = parts[i].to_affixes();
+        let mut nodes = Vec::new();
+        if let Some(prefix) = affixes.prefix.filter(|_| !trim_prefix || k > 0) {
+            nodes.push(Node::Text(prefix));
+        }
+        nodes.extend(rendered[i].iter().flatten().cloned());
+        if let Some(suffix) =
+            affixes.suffix.filter(|_| !trim_suffix || k + 1 < present.len())
+        {
+            nodes.push(Node::Text(suffix));
+        }
+        joined.push(nodes);
+    }
+
+    join(joined, delimiter)
+}
@@ -0,0 +1,419 @@
+//! Disambiguation of cites that would otherwise render the same.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::{Cite, Output, Processor, RenderError, Renderer};
+use crate::json::{self, Item, NameItem, NameValue, Value};
+use crate::taxonomy::NameVariable;
+use crate::{CitePosition, DisambiguationRule};
+
+/// How the cites of an item are rendered to set them apart from the cites of
+/// other items that would look the same.
+///
+/// This is computed by [`Processor::disambiguate`].
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct Disambiguation {
+    /// The minimum number of names that are shown before a name list is
+    /// truncated with et al.
+    pub names: Option<usize>,
+    /// The names whose given names are expanded, identified by their
+    /// variable and their index in the name list.
+    pub given_names: Vec<(NameVariable, usize, GivenName)>,
+    /// Whether the `disambiguate` condition of `cs:choose` is true.
+    pub condition: bool,
+    /// The index of the year suffix, where 0 is "a".
+    pub year_suffix: Option<usize>,
+}
+
+impl Disambiguation {
+    /// The state of items that need no disambiguation.
+    pub(super) const NONE: Self = Self {
+        names: None,
+        given_names: Vec::new(),
+        condition: false,
+        year_suffix: None,
+    };
+
+    /// How far the given name of a name in a name variable is expanded.
+    pub fn given_name(&self, variable: NameVariable, index: usize) -> Option<GivenName> {
+        self.given_names
+            .iter()
+            .filter(|&&(v, i, _)| v == variable && i == index)
+            .map(|&(.., given)| given)
+            .max()
+    }
+}
+
+/// How much of a given name is shown to disambiguate a name.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum GivenName {
+    /// The given name is shown, but initialized if the style initializes
+    /// names.
+    Initials,
+    /// The full given name is shown.
+    Full,
+}
+
+impl Processor<'_> {
+    /// Disambiguate the items cited in a sequence of citation clusters.
+    ///
+    /// The methods enabled by the `disambiguate-add-names`,
+    /// `disambiguate-add-givenname`, and `disambiguate-add-year-suffix`
+    /// attributes of the citation are tried in that order, and the
+    /// `disambiguate` condition is true for the cites that stay ambiguous
+    /// after names were added. Year suffixes follow the order of the
+    /// bibliography. The result applies to all citations and bibliography
+    /// entries rendered afterwards.
+    ///
+    /// This first numbers the items with [`Processor::assign_numbers`] since
+    /// the cites may contain their numbers.
+    pub fn disambiguate(
+        &mut self,
+        citations: &[json::Citation],
+    ) -> Result<(), RenderError> {
+        self.assign_numbers(citations)?;
+        self.disambiguation = self.compute_disambiguation(citations)?;
+        Ok(())
+    }
+
+    /// How the item with the given ID was disambiguated.
+    pub fn disambiguation(&self, id: &str) -> Option<&Disambiguation> {
+        self.disambiguation.get(self.ids.get(id)?)
+    }
+
+    /// The disambiguation of the item at an index.
+    pub(super) fn disambiguation_of(&self, index: usize) -> &Disambiguation {
+        static NONE: Disambiguation = Disambiguation::NONE;
+        self.disambiguation.get(&index).unwrap_or(&NONE)
+    }
+
+    fn compute_disambiguation(
+        &self,
+        citations: &[json::Citation],
+    ) -> Result<HashMap<usize, Disambiguation>, RenderError> {
+        let mut cited = Vec::new();
+        for citation_item in citations.iter().flat_map(|c| &c.citation_items) {
+            let index = self.index(&citation_item.id)?;
+            if !cited.contains(&index) {
+                cited.push(index);
+            }
+        }
+
+        let mut states: HashMap<usize, Disambiguation> =
+            cited.iter().map(|&i| (i, Disambiguation::default())).collect();
+        let citation = &self.style.citation;
+        let rule = citation.givenname_disambiguation_rule;
+
+        if citation.disambiguate_add_givenname && rule != DisambiguationRule::ByCite {
+            self.expand_ambiguous_names(&cited, &mut states, rule);
+        }
+
+        if citation.disambiguate_add_names {
+            for group in self.ambiguous(&cited, &states)? {
+                self.add_names(&group, &mut states)?;
+            }
+        }
+
+        if citation.disambiguate_add_givenname {
+            for group in self.ambiguous(&cited, &states)? {
+                self.add_given_names(&group, &mut states, rule)?;
+            }
+        }
+
+        let groups = self.ambiguous(&cited, &states)?;
+        for i in groups.iter().flatten() {
+            states.get_mut(i).unwrap().condition = true;
+        }
+
+        if citation.disambiguate_add_year_suffix {
+            let order = self.bibliography_order()?;
+            for mut group in self.ambiguous(&cited, &states)? {
+                group.sort_by_key(|i| order.iter().position(|j| j == i));
+                for (k, i) in group.iter().enumerate() {
+                    states.get_mut(i).unwrap().year_suffix = Some(k);
+                }
+            }
+        }
+
+        states.retain(|_, state| *state != Disambiguation::NONE);
+        Ok(states)
+    }
+
+    /// Expand the given names of all cited names that share their family
+    /// name with a different name. Initials suffice if they differ or if the
+    /// rule does not allow full given names.
+    fn expand_ambiguous_names(
+        &self,
+        cited: &[usize],
+        states: &mut HashMap<usize, Disambiguation>,
+        rule: DisambiguationRule,
+    ) {
+        let primary = !rule.allows_multiple_names();
+        let names: Vec<_> = cited
+            .iter()
+            .flat_map(|&i| {
+                personal_names(&self.items[i], primary)
+                    .map(move |(variable, index, name)| (i, variable, index, name))
+            })
+            .collect();
+
+        for &(i, variable, index, name) in &names {
+            let others: Vec<&NameItem> = names
+                .iter()
+                .map(|&(.., other)| other)
+                .filter(|other| {
+                    other.family == name.family
+                        && other.non_dropping_particle == name.non_dropping_particle
+                        && other.given != name.given
+                })
+                .collect();
+
+            if others.is_empty() {
+                continue;
+            }
+
+            let same_initials =
+                others.iter().any(|other| initials(other) == initials(name));
+            let given = if same_initials && rule.allows_full_first_names() {
+                GivenName::Full
+            } else {
+                GivenName::Initials
+            };
+
+            states.get_mut(&i).unwrap().given_names.push((variable, index, given));
+        }
+    }
+
+    /// Show more names in the cites of a group until they differ. Cites that
+    /// stay ambiguous keep their usual number of names.
+    fn add_names(
+        &self,
+        group: &[usize],
+        states: &mut HashMap<usize, Disambiguation>,
+    ) -> Result<(), RenderError> {
+        let max = group.iter().map(|&i| name_count(&self.items[i])).max().unwrap_or(0);
+        let mut pending = group.to_vec();
+        for n in 1..=max {
+            for i in &pending {
+                states.get_mut(i).unwrap().names = Some(n);
+            }
+
+            let mut ambiguous = Vec::new();
+            for &i in &pending {
+                if !self.is_unique(i, group, states)? {
+                    ambiguous.push(i);
+                }
+            }
+
+            pending = ambiguous;
+            if pending.is_empty() {
+                break;
+            }
+        }
+
+        for i in &pending {
+            states.get_mut(i).unwrap().names = None;
+        }
+
+        Ok(())
+    }
+
+    /// Expand the given names in the cites of a group, name by name, until
+    /// they differ. A name is expanded in all cites that are still ambiguous
+    /// so that equal names stay equal. Cites that stay ambiguous lose their
+    /// expansions again.
+    fn add_given_names(
+        &self,
+        group: &[usize],
+        states: &mut HashMap<usize, Disambiguation>,
+        rule: DisambiguationRule,
+    ) -> Result<(), RenderError> {
+        let levels: &[GivenName] = if rule.allows_full_first_names() {
+            &[GivenName::Initials, GivenName::Full]
+        } else {
+            &[GivenName::Initials]
+        };
+
+        let mut names = Vec::new();
+        for &i in group {
+            for (variable, index, _) in
+                personal_names(&self.items[i], !rule.allows_multiple_names())
+            {
+                if !names.contains(&(variable, index)) {
+                    names.push((variable, index));
+                }
+            }
+        }
+
+        let before: HashMap<usize, Vec<_>> =
+            group.iter().map(|&i| (i, states[&i].given_names.clone())).collect();
+        let mut pending = group.to_vec();
+        'names: for (variable, index) in names {
+            for &given in levels {
+                for i in &pending {
+                    states.get_mut(i).unwrap().given_names.push((variable, index, given));
+                }
+
+                let mut ambiguous = Vec::new();
+                for &i in &pending {
+                    if !self.is_unique(i, group, states)? {
+                        ambiguous.push(i);
+                    }
+                }
+
+                pending = ambiguous;
+                if pending.is_empty() {
+                    break 'names;
+                }
+            }
+        }
+
+        for i in pending {
+            states.get_mut(&i).unwrap().given_names = before[&i].clone();
+        }
+
+        Ok(())
+    }
+
+    /// The groups of cited items whose cites render the same, in the order
+    /// in which they were first cited.
+    fn ambiguous(
+        &self,
+        cited: &[usize],
+        states: &HashMap<usize, Disambiguation>,
+    ) -> Result<Vec<Vec<usize>>, RenderError> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut texts: HashMap<String, usize> = HashMap::new();
+        for &i in cited {
+            let text = self.render_plain(i, &states[&i])?;
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            match texts.get(&text) {
+                Some(&k) => groups[k].push(i),
+                None => {
+                    texts.insert(text, groups.len());
+                    groups.push(vec![i]);
+                }
+            }
+        }
+
+        groups.retain(|group| group.len() > 1);
+        Ok(groups)
+    }
+
+    /// Whether the cite of an item differs from the other cites of a group.
+    fn is_unique(
+        &self,
+        item: usize,
+        group: &[usize],
+        states: &HashMap<usize, Disambiguation>,
+    ) -> Result<bool, RenderError> {
+        let text = self.render_plain(item, &states[&item])?;
+        for &other in group.iter().filter(|&&other| other != item) {
+            if self.render_plain(other, &states[&other])? == text {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Render the first cite of an item without a locator as plain text.
+    fn render_plain(
+        &self,
+        index: usize,
+        disambiguation: &Disambiguation,
+    ) -> Result<String, RenderError> {
+        let cite = Cite {
+            item: &self.items[index],
+            number: self.numbers[index],
+            locator: None,
+            position: Some(CitePosition::First),
+            near_note: false,
+            first_reference_note_number: None,
+            disambiguation,
+        };
+
+        let name_options =
+            self.style.settings.options.apply(&self.style.citation.name_options);
+        let mut renderer = Renderer::new(self, &cite, &name_options);
+        let nodes = renderer.render_layout(&self.style.citation.layout)?;
+        Ok(Output(nodes).to_plain())
+    }
+}
+
+/// The letters of a year suffix: "a" to "z", then "aa", "ab", and so on.
+pub(super) fn year_suffix(index: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        letters.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).unwrap()
+}
+
+/// The names of an item that have a given name, with their variable and their
+/// index in the name list. Only the first name of each list is included if
+/// `primary` is set.
+fn personal_names(
+    item: &Item,
+    primary: bool,
+) -> impl Iterator<Item = (NameVariable, usize, &NameItem)> {
+    item.0
+        .iter()
+        .filter_map(|(key, value)| match value {
+            Value::Names(names) => Some((NameVariable::from_str(key).ok()?, names)),
+            _ => None,
+        })
+        .flat_map(move |(variable, names)| {
+            let names = if primary { &names[..names.len().min(1)] } else { names };
+            names.iter().enumerate().filter_map(move |(index, name)| match name {
+                NameValue::Item(name)
+                    if name.given.as_ref().is_some_and(|g| !g.is_empty()) =>
+                {
+                    Some((variable, index, name))
+                }
+                _ => None,
+            })
+        })
+}
+
+/// The length of the longest name list of an item.
+fn name_count(item: &Item) -> usize {
+    item.0
+        .values()
+        .map(|value| match value {
+            Value::Names(names) => names.len(),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// The initials of a given name.
+fn initials(name: &NameItem) -> String {
+    name.given
+        .iter()
+        .flat_map(|given| given.split([' ', '-', '.']))
+        .filter_map(|word| word.chars().next())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(year_suffix(0), "a");
+        assert_eq!(year_suffix(25), "z");
+        assert_eq!(year_suffix(26), "aa");
+        assert_eq!(year_suffix(27), "ab");
+        assert_eq!(year_suffix(26 + 26 * 26), "aaa");
+    }
+}
@@ -0,0 +1,1256 @@
+//! A CSL processor that renders citations and bibliographies.
+//!
+//! The [`Processor`] takes an [`IndependentStyle`], a [`LocaleResolver`] for
+//! the style's localization data, and the cited items as CSL-JSON. It renders
+//! [`json::Citation`] clusters into citations and all items into bibliography
+//! entries. The results are [`Output`] trees that can be written as HTML or
+//! plain text.
+//!
+//! This is only available when the `render` feature is enabled.
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use citationberg::json::Item;
+//! use citationberg::render::Processor;
+//! use citationberg::{IndependentStyle, LocaleFile};
+//!
+//! let style = IndependentStyle::from_xml(
+//!     r#"<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+//!   <info><id/><title/></info>
+//!   <citation>
+//!     <layout prefix="(" suffix=")">
+//!       <text variable="title" font-style="italic"/>
+//!     </layout>
+//!   </citation>
+//! </style>"#,
+//! )?;
+//! let en_us = LocaleFile::from_xml(&std::fs::read_to_string(
+//!     "tests/locales/locales-en-US.xml",
+//! )?)?;
+//! let files = [en_us];
+//! let items: Vec<Item> =
+//!     serde_json::from_str(r#"[{"id": "a", "type": "book", "title": "Dune"}]"#)?;
+//!
+//! let processor = Processor::new(&style, style.locale_resolver(None, &files), &items);
+//! let citation = serde_json::from_str(
+//!     r#"{"citationID": "c1", "citationItems": [{"id": "a"}], "properties": {}}"#,
+//! )?;
+//! let output = processor.render_citations(&[citation])?;
+//! assert_eq!(output[0].to_html(), "(<i>Dune</i>)");
+//! # Ok(())
+//! # }
+//! ```
+
+mod case;
+mod collapse;
+mod date;
+mod disambiguate;
+mod names;
+mod numbering;
+mod output;
+mod position;
+mod sort;
+mod substitute;
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+pub use self::case::{change_case, is_english};
+pub use self::collapse::{ClusterCite, collapse};
+pub use self::disambiguate::{Disambiguation, GivenName};
+pub use self::names::{FormattedName, NameFormatter, NameList, NameListItem, NamePiece};
+pub use self::output::{Html, Latex, Node, Output, OutputFormat, PlainText, Rtf, Typst};
+pub use self::position::{PositionTracker, TrackedPosition};
+pub use self::sort::Collator;
+pub use self::substitute::{AuthorSubstitution, substitute_authors};
+
+use crate::json::{
+    self, DateValue, FixedDateRange, Item, NameValue, RichNode, RichText, Value,
+};
+use crate::locale::LocaleResolver;
+use crate::taxonomy::{
+    DateVariable, Kind, Locator, NameVariable, NumberOrPageVariable, NumberVariable,
+    OtherTerm, PageVariable, StandardVariable, Term, Variable,
+};
+use crate::{
+    Affixes, CitationContext, CitePosition, Display, Formatting, IndependentStyle,
+    InheritableNameOptions, LabelPluralize, Layout, LayoutRenderingElement,
+    LongShortForm, NumberForm, SortKey, TermForm, Text, TextCase, TextTarget, ToAffixes,
+    ToFormatting, VariablelessLabel,
+};
+
+/// How deeply macros may call each other before the processor assumes that
+/// they recurse.
+const MAX_MACRO_DEPTH: usize = 64;
+
+/// Renders citations and bibliographies for a style.
+#[derive(Debug, Clone)]
+pub struct Processor<'a> {
+    style: &'a IndependentStyle,
+    locale: LocaleResolver<'a>,
+    items: &'a [Item],
+    ids: HashMap<String, usize>,
+    links: bool,
+    /// The disambiguation of cited items, by their index.
+    disambiguation: HashMap<usize, Disambiguation>,
+    /// Whether the style renders the `year-suffix` variable itself. If it
+    /// does not, year suffixes are appended to the first rendered date.
+    explicit_year_suffix: bool,
+    /// The indices of the items in the order they were first cited,
+    /// followed by the items that were not cited.
+    cite_order: Vec<usize>,
+    /// The citation number of each item, by its index.
+    numbers: Vec<usize>,
+}
+
+impl<'a> Processor<'a> {
+    /// Create a processor for the style with its localization data and the
+    /// items that can be cited.
+    ///
+    /// Items without an ID can only appear in the bibliography.
+    pub fn new(
+        style: &'a IndependentStyle,
+        locale: LocaleResolver<'a>,
+        items: &'a [Item],
+    ) -> Self {
+        let mut ids = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if let Some(id) = item.id() {
+                ids.entry(id.into_owned()).or_insert(i);
+            }
+        }
+
+        let explicit_year_suffix =
+            style.usage().standard.contains(&StandardVariable::YearSuffix);
+
+        Self {
+            style,
+            locale,
+            items,
+            ids,
+            links: false,
+            disambiguation: HashMap::new(),
+            explicit_year_suffix,
+            cite_order: (0..items.len()).collect(),
+            numbers: (1..=items.len()).collect(),
+        }
+    }
+
+    /// Link the `URL` and `DOI` variables to their targets.
+    pub fn with_links(mut self, links: bool) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// The style the processor renders with.
+    pub fn style(&self) -> &'a IndependentStyle {
+        self.style
+    }
+
+    /// The item with the given ID.
+    pub fn item(&self, id: &str) -> Option<&'a Item> {
+        self.ids.get(id).map(|&i| &self.items[i])
+    }
+
+    /// Render a sequence of citation clusters in document order.
+    ///
+    /// Returns one output per cluster. The positions of the cites are
+    /// tracked across the clusters unless the citation items specify them.
+    /// Call [`Processor::disambiguate`] before to number the items and
+    /// disambiguate the cites.
+    pub fn render_citations(
+        &self,
+        citations: &[json::Citation],
+    ) -> Result<Vec<Output>, RenderError> {
+        let mut tracker = PositionTracker::new(self.style.citation.near_note_distance);
+        citations
+            .iter()
+            .map(|citation| self.render_citation(citation, &tracker.track(citation)))
+            .collect()
+    }
+
+    /// Render the bibliography entries of all items.
+    ///
+    /// The entries are sorted by the bibliography's sort keys. Names that
+    /// repeat those of the previous entry are replaced with the
+    /// `subsequent-author-substitute`. Returns no entries if the style does
+    /// not define a bibliography.
+    pub fn render_bibliography(&self) -> Result<Vec<Entry>, RenderError> {
+        let Some(bibliography) = &self.style.bibliography else {
+            return Ok(Vec::new());
+        };
+
+        let name_options = self.style.settings.options.apply(&bibliography.name_options);
+        let order = self.bibliography_order()?;
+        let mut substitutions = vec![None; order.len()];
+        if bibliography.subsequent_author_substitute.is_some() {
+            let mut lists = Vec::with_capacity(order.len());
+            for &i in &order {
+                let cite = self.bibliography_cite(i);
+                let mut renderer = Renderer::new(self, &cite, &name_options);
+                renderer.render_layout(&bibliography.layout)?;
+                lists.push(renderer.rendered_names);
+            }
+
+            substitutions = substitute_authors(
+                bibliography.subsequent_author_substitute_rule,
+                &lists,
+            );
+        }
+
+        let mut entries = Vec::new();
+        for (i, substitution) in order.into_iter().zip(substitutions) {
+            let item = &self.items[i];
+            let cite = self.bibliography_cite(i);
+            let mut renderer = Renderer::new(self, &cite, &name_options);
+            renderer.author_substitution = substitution;
+            let nodes = renderer.render_layout(&bibliography.layout)?;
+            let nodes = self.finish_layout(nodes, &bibliography.layout);
+            entries.push(Entry {
+                id: item.id().map(Cow::into_owned),
+                output: Output(nodes),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// The indices of the items in the order of the bibliography: sorted by
+    /// the bibliography's sort keys, or in the order they were first cited if
+    /// there are none.
+    fn bibliography_order(&self) -> Result<Vec<usize>, RenderError> {
+        let mut order = self.cite_order.clone();
+        let Some(bibliography) = &self.style.bibliography else { return Ok(order) };
+        let Some(sort) = &bibliography.sort else { return Ok(order) };
+
+        let name_options = self.style.settings.options.apply(&bibliography.name_options);
+        let mut values = Vec::with_capacity(self.items.len());
+        for i in 0..self.items.len() {
+            values.push(self.sort_values(
+                &self.bibliography_cite(i),
+                sort,
+                &name_options,
+            )?);
+        }
+
+        let collator = self.collator();
+        order.sort_by(|&a, &b| {
+            self.compare_sort_values(&collator, sort, &values[a], &values[b])
+        });
+        Ok(order)
+    }
+
+    /// The cite of an item in the bibliography.
+    fn bibliography_cite(&self, index: usize) -> Cite<'_> {
+        Cite {
+            item: &self.items[index],
+            number: self.numbers[index],
+            locator: None,
+            position: None,
+            near_note: false,
+            first_reference_note_number: None,
+            disambiguation: self.disambiguation_of(index),
+        }
+    }
+
+    /// Apply the layout's formatting and affixes and clean up the result.
+    fn finish_layout(&self, nodes: Vec<Node>, layout: &Layout) -> Vec<Node> {
+        let attrs = Attributes {
+            formatting: layout.to_formatting(),
+            affixes: layout.to_affixes(),
+            ..Attributes::default()
+        };
+
+        let mut nodes = self.decorate(nodes, attrs);
+        self.nest_quotes(&mut nodes, false);
+        let punctuation_in_quote =
+            self.locale.options().punctuation_in_quote.unwrap_or(false);
+        output::normalize(&mut nodes, punctuation_in_quote);
+        nodes
+    }
+
+    /// Apply the common rendering attributes of an element to its content.
+    fn decorate(&self, mut nodes: Vec<Node>, attrs: Attributes) -> Vec<Node> {
+        if is_empty(&nodes) {
+            return Vec::new();
+        }
+
+        if let Some(case) = attrs.text_case {
+            apply_text_case(&mut nodes, case);
+        }
+
+        if attrs.strip_periods {
+            for_each_text(&mut nodes, &mut |text| text.retain(|c| c != '.'));
+        }
+
+        if attrs.quotes {
+            nodes = vec![self.quote(nodes, false)];
+        }
+
+        if !attrs.formatting.is_empty() {
+            nodes = vec![Node::Formatted(attrs.formatting, nodes)];
+        }
+
+        if let Some(prefix) = attrs.affixes.prefix {
+            nodes.insert(0, Node::Text(prefix));
+        }
+
+        if let Some(suffix) = attrs.affixes.suffix {
+            nodes.push(Node::Text(suffix));
+        }
+
+        if let Some(display) = attrs.display {
+            nodes = vec![Node::Display(display, nodes)];
+        }
+
+        nodes
+    }
+
+    /// Wrap nodes in the outer or inner quotation marks of the locale.
+    fn quote(&self, children: Vec<Node>, inner: bool) -> Node {
+        let (open, close, fallback) = if inner {
+            (OtherTerm::OpenInnerQuote, OtherTerm::CloseInnerQuote, ("‘", "’"))
+        } else {
+            (OtherTerm::OpenQuote, OtherTerm::CloseQuote, ("“", "”"))
+        };
+
+        let term = |term: OtherTerm, fallback: &str| {
+            self.locale
+                .localize(term.into(), TermForm::Long, false, None)
+                .unwrap_or(fallback)
+                .to_string()
+        };
+
+        Node::Quoted {
+            open: term(open, fallback.0),
+            close: term(close, fallback.1),
+            children,
+        }
+    }
+
+    /// Use inner quotation marks for quotes nested in other quotes.
+    fn nest_quotes(&self, nodes: &mut [Node], inner: bool) {
+        for node in nodes {
+            if let Node::Quoted { children, .. } = node {
+                self.nest_quotes(children, !inner);
+                if inner {
+                    *node = self.quote(std::mem::take(children), true);
+                }
+            } else if let Some(children) = node.children_mut() {
+                self.nest_quotes(children, inner);
+            }
+        }
+    }
+
+    fn render_citation(
+        &self,
+        citation: &json::Citation,
+        positions: &[TrackedPosition],
+    ) -> Result<Output, RenderError> {
+        let layout = &self.style.citation.layout;
+        let name_options =
+            self.style.settings.options.apply(&self.style.citation.name_options);
+
+        let mut cites = Vec::new();
+        for (citation_item, tracked) in citation.citation_items.iter().zip(positions) {
+            let index = self.index(&citation_item.id)?;
+            let item = &self.items[index];
+            let disambiguation = self.disambiguation_of(index);
+            let cite = Cite::new(
+                item,
+                self.numbers[index],
+                citation_item,
+                tracked,
+                disambiguation,
+            );
+            cites.push((cite, citation_item));
+        }
+
+        if let Some(sort) = &self.style.citation.sort {
+            let mut keyed = Vec::with_capacity(cites.len());
+            for (cite, citation_item) in cites {
+                let values = self.sort_values(&cite, sort, &name_options)?;
+                keyed.push((values, cite, citation_item));
+            }
+
+            let collator = self.collator();
+            keyed.sort_by(|a, b| self.compare_sort_values(&collator, sort, &a.0, &b.0));
+            cites = keyed.into_iter().map(|(_, cite, item)| (cite, item)).collect();
+        }
+
+        let mut rendered = Vec::new();
+        for (cite, citation_item) in &cites {
+            rendered.extend(self.cluster_cite(cite, citation_item, &name_options)?);
+        }
+
+        let range_delimiter = self
+            .locale
+            .localize(
+                OtherTerm::CitationRangeDelimiter.into(),
+                TermForm::Long,
+                false,
+                None,
+            )
+            .unwrap_or("–");
+        let nodes = collapse(&self.style.citation, rendered, range_delimiter);
+        Ok(Output(self.finish_layout(nodes, layout)))
+    }
+
+    /// The index of the item with the given ID.
+    fn index(&self, id: &str) -> Result<usize, RenderError> {
+        self.ids
+            .get(id)
+            .copied()
+            .ok_or_else(|| RenderError::UnknownItem(id.to_string()))
+    }
+}
+
+/// A rendered bibliography entry.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Entry {
+    /// The ID of the item.
+    pub id: Option<String>,
+    /// The formatted entry.
+    pub output: Output,
+}
+
+/// An error that occurs while rendering.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RenderError {
+    /// A citation refers to an item that was not passed to the processor.
+    UnknownItem(String),
+    /// The style calls a macro that is not defined.
+    UndefinedMacro(String),
+    /// The style's macros call each other without end.
+    RecursiveMacro(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownItem(id) => write!(f, "no item with the ID `{id}`"),
+            Self::UndefinedMacro(name) => write!(f, "macro `{name}` is not defined"),
+            Self::RecursiveMacro(name) => write!(f, "macro `{name}` calls itself"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// An item together with the data that depends on where it is cited.
+struct Cite<'a> {
+    item: &'a Item,
+    /// The 1-based position of the item in the bibliography.
+    number: usize,
+    /// The locator value and its label. Unknown labels are dropped.
+    locator: Option<(Option<Locator>, &'a str)>,
+    position: Option<CitePosition>,
+    near_note: bool,
+    first_reference_note_number: Option<u32>,
+    disambiguation: &'a Disambiguation,
+}
+
+impl<'a> Cite<'a> {
+    fn new(
+        item: &'a Item,
+        number: usize,
+        citation_item: &'a json::CitationItem,
+        tracked: &TrackedPosition,
+        disambiguation: &'a Disambiguation,
+    ) -> Self {
+        let locator =
+            citation_item
+                .locator
+                .as_deref()
+                .filter(|l| !l.is_empty())
+                .map(|value| {
+                    let label = match citation_item.label.as_deref() {
+                        Some(label) => Locator::from_str(label).ok(),
+                        None => Some(Locator::Page),
+                    };
+                    (label, value)
+                });
+
+        // Positions in the citation item override the tracked ones.
+        let position = match citation_item.position {
+            Some(0) => CitePosition::First,
+            Some(1) => CitePosition::Subsequent,
+            Some(2) => CitePosition::Ibid,
+            Some(3) => CitePosition::IbidWithLocator,
+            _ => tracked.position,
+        };
+
+        Self {
+            item,
+            number,
+            locator,
+            position: Some(position),
+            near_note: citation_item.near_note.unwrap_or(tracked.near_note),
+            first_reference_note_number: tracked.first_reference_note_number,
+            disambiguation,
+        }
+    }
+}
+
+/// Renders the elements of a layout for a single cite.
+struct Renderer<'a> {
+    processor: &'a Processor<'a>,
+    cite: &'a Cite<'a>,
+    /// The name options of the style and the citation or bibliography.
+    name_options: &'a InheritableNameOptions,
+    /// How many variables have been looked up by rendering elements.
+    called: usize,
+    /// How many of the looked up variables were non-empty.
+    rendered: usize,
+    /// Variables that were used by a `cs:substitute` element and must not be
+    /// rendered again.
+    suppressed: HashSet<Variable>,
+    /// The non-empty variables rendered since recording started.
+    recording: Option<Vec<Variable>>,
+    /// Whether the first `cs:names` element must not be rendered.
+    suppress_author: bool,
+    /// Whether the year suffix still has to be appended to a date because
+    /// the style does not render it.
+    implicit_year_suffix: bool,
+    /// The key whose value is rendered, if the renderer builds a sort key.
+    sort_key: Option<&'a SortKey>,
+    /// The output of the first `cs:names` element, even if it was
+    /// suppressed.
+    first_names: Option<Vec<Node>>,
+    /// How the first `cs:names` element is replaced with the
+    /// `subsequent-author-substitute`, if it repeats the names of the
+    /// previous bibliography entry.
+    author_substitution: Option<AuthorSubstitution>,
+    /// The names rendered by the first `cs:names` element or its
+    /// substitute, before they were replaced.
+    rendered_names: Option<NameList>,
+    macro_depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(
+        processor: &'a Processor<'a>,
+        cite: &'a Cite<'a>,
+        name_options: &'a InheritableNameOptions,
+    ) -> Self {
+        Self {
+            processor,
+            cite,
+            name_options,
+            called: 0,
+            rendered: 0,
+            suppressed: HashSet::new(),
+            recording: None,
+            suppress_author: false,
+            implicit_year_suffix: cite.disambiguation.year_suffix.is_some()
+                && !processor.explicit_year_suffix,
+            sort_key: None,
+            first_names: None,
+            author_substitution: None,
+            rendered_names: None,
+            macro_depth: 0,
+        }
+    }
+
+    fn locale(&self) -> &LocaleResolver<'a> {
+        &self.processor.locale
+    }
+
+    /// Apply the attributes of an element. Title and sentence case only apply
+    /// to English items, which are items in English or without a language in a
+    /// style for English.
+    fn decorate(&self, nodes: Vec<Node>, mut attrs: Attributes) -> Vec<Node> {
+        if attrs.text_case.is_some_and(|case| !case.is_language_independent()) {
+            let english = match self.field("language").and_then(Value::to_str) {
+                Some(language) => is_english(&language),
+                None => {
+                    let default = self.processor.style.default_locale.as_ref();
+                    default.is_none_or(|locale| is_english(&locale.0))
+                }
+            };
+
+            if !english {
+                attrs.text_case = None;
+            }
+        }
+
+        self.processor.decorate(nodes, attrs)
+    }
+
+    fn render_layout(&mut self, layout: &Layout) -> Result<Vec<Node>, RenderError> {
+        self.render_elements(&layout.elements, None)
+    }
+
+    fn render_elements(
+        &mut self,
+        elements: &[LayoutRenderingElement],
+        delimiter: Option<&str>,
+    ) -> Result<Vec<Node>, RenderError> {
+        let mut parts = Vec::new();
+        for element in elements {
+            parts.push(self.render_element(element)?);
+        }
+        Ok(join(parts, delimiter))
+    }
+
+    fn render_element(
+        &mut self,
+        element: &LayoutRenderingElement,
+    ) -> Result<Vec<Node>, RenderError> {
+        Ok(match element {
+            LayoutRenderingElement::Text(text) => self.render_text(text)?,
+            LayoutRenderingElement::Date(date) => self.render_date(date),
+            LayoutRenderingElement::Number(number) => {
+                let variable = Variable::from(number.variable);
+                let value = self.variable(variable);
+                self.track(variable, value.is_some());
+                let Some(value) = value else { return Ok(Vec::new()) };
+
+                let text = match number.variable {
+                    _ if self.sort_key.is_some() => value.into_owned(),
+                    NumberOrPageVariable::Page(_) => self.format_page(&value),
+                    NumberOrPageVariable::Number(n) => {
+                        self.format_number(&value, number.form, Term::from(n))
+                    }
+                };
+
+                self.decorate(
+                    vec![Node::Text(text)],
+                    Attributes {
+                        formatting: number.to_formatting(),
+                        affixes: number.to_affixes(),
+                        display: number.display,
+                        text_case: number.text_case,
+                        ..Attributes::default()
+                    },
+                )
+            }
+            LayoutRenderingElement::Names(names) => self.render_names(names)?,
+            LayoutRenderingElement::Label(label) => {
+                self.render_label(label.variable, &label.label)
+            }
+            LayoutRenderingElement::Group(group) => {
+                let (called, rendered) = (self.called, self.rendered);
+                let children =
+                    self.render_elements(&group.children, group.delimiter.as_deref())?;
+                if self.called > called && self.rendered == rendered {
+                    return Ok(Vec::new());
+                }
+
+                self.decorate(
+                    children,
+                    Attributes {
+                        formatting: group.to_formatting(),
+                        affixes: group.to_affixes(),
+                        display: group.display,
+                        ..Attributes::default()
+                    },
+                )
+            }
+            LayoutRenderingElement::Choose(choose) => match choose.select(&*self) {
+                Some(children) => self.render_elements(children, None)?,
+                None => Vec::new(),
+            },
+        })
+    }
+
+    fn render_text(&mut self, text: &Text) -> Result<Vec<Node>, RenderError> {
+        let content = match &text.target {
+            TextTarget::Variable { var, form } => {
+                let value = self.text_variable(*var, *form);
+                self.track(*var, value.is_some());
+                match value {
+                    Some(value) if self.processor.links => match var {
+                        Variable::Standard(StandardVariable::URL) => {
+                            vec![Node::Link {
+                                url: value.to_string(),
+                                children: rich_nodes(&value),
+                            }]
+                        }
+                        Variable::Standard(StandardVariable::DOI) => {
+                            let url = if value.starts_with("http") {
+                                value.to_string()
+                            } else {
+                                format!("https://doi.org/{value}")
+                            };
+                            vec![Node::Link { url, children: rich_nodes(&value) }]
+                        }
+                        _ => rich_nodes(&value),
+                    },
+                    Some(value) => rich_nodes(&value),
+                    None => Vec::new(),
+                }
+            }
+            TextTarget::Macro { name } => self.render_macro(name)?,
+            TextTarget::Term { term, form, plural } => self
+                .locale()
+                .localize(*term, *form, *plural, None)
+                .map(|s| vec![Node::Text(s.to_string())])
+                .unwrap_or_default(),
+            TextTarget::Value { val } => vec![Node::Text(val.clone())],
+        };
+
+        Ok(self.decorate(
+            content,
+            Attributes {
+                formatting: text.to_formatting(),
+                affixes: text.to_affixes(),
+                display: text.display,
+                quotes: text.quotes,
+                strip_periods: text.strip_periods,
+                text_case: text.text_case,
+            },
+        ))
+    }
+
+    /// Render a macro. Like a group, a macro is suppressed if it calls
+    /// variables and all of them are empty.
+    fn render_macro(&mut self, name: &str) -> Result<Vec<Node>, RenderError> {
+        let style = self.processor.style;
+        let Some(m) = style.macros.iter().find(|m| m.name == name) else {
+            return Err(RenderError::UndefinedMacro(name.to_string()));
+        };
+
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            return Err(RenderError::RecursiveMacro(name.to_string()));
+        }
+
+        let (called, rendered) = (self.called, self.rendered);
+        self.macro_depth += 1;
+        let nodes = self.render_elements(&m.children, None);
+        self.macro_depth -= 1;
+
+        let nodes = nodes?;
+        if self.called > called && self.rendered == rendered {
+            return Ok(Vec::new());
+        }
+
+        Ok(nodes)
+    }
+
+    fn render_label(
+        &mut self,
+        variable: NumberOrPageVariable,
+        label: &VariablelessLabel,
+    ) -> Vec<Node> {
+        let Some(value) = self.variable(variable.into()) else { return Vec::new() };
+        let term = match variable {
+            NumberOrPageVariable::Number(NumberVariable::Locator) => {
+                match self.cite.locator {
+                    Some((Some(Locator::Custom) | None, _)) | None => return Vec::new(),
+                    Some((Some(locator), _)) => Term::Locator(locator),
+                }
+            }
+            _ => Term::from(variable),
+        };
+
+        let plural = match label.plural {
+            LabelPluralize::Always => true,
+            LabelPluralize::Never => false,
+            LabelPluralize::Contextual => is_plural(variable.into(), &value),
+        };
+
+        self.render_term_label(term, plural, label)
+    }
+
+    /// Render the term of a label.
+    fn render_term_label(
+        &self,
+        term: Term,
+        plural: bool,
+        label: &VariablelessLabel,
+    ) -> Vec<Node> {
+        let Some(text) = self.locale().localize(term, label.form, plural, None) else {
+            return Vec::new();
+        };
+
+        self.decorate(
+            vec![Node::Text(text.to_string())],
+            Attributes {
+                formatting: label.to_formatting(),
+                affixes: label.to_affixes(),
+                strip_periods: label.strip_periods,
+                text_case: label.text_case,
+                ..Attributes::default()
+            },
+        )
+    }
+
+    /// Record that a rendering element looked up a variable.
+    fn track(&mut self, variable: Variable, rendered: bool) {
+        self.called += 1;
+        if rendered {
+            self.rendered += 1;
+            if let Some(recording) = &mut self.recording {
+                recording.push(variable);
+            }
+        }
+    }
+
+    /// The raw value of an item field.
+    fn field(&self, key: &str) -> Option<&'a Value> {
+        self.cite.item.0.get(key)
+    }
+
+    /// The value of a standard, number, or page variable.
+    fn variable(&self, variable: Variable) -> Option<Cow<'a, str>> {
+        if self.suppressed.contains(&variable) {
+            return None;
+        }
+
+        let value = match variable {
+            Variable::Number(NumberVariable::Locator) => {
+                Cow::Borrowed(self.cite.locator?.1)
+            }
+            Variable::Number(NumberVariable::CitationNumber) => {
+                Cow::Owned(self.cite.number.to_string())
+            }
+            Variable::Number(NumberVariable::FirstReferenceNoteNumber) => {
+                Cow::Owned(self.cite.first_reference_note_number?.to_string())
+            }
+            Variable::Standard(StandardVariable::YearSuffix) => Cow::Owned(
+                disambiguate::year_suffix(self.cite.disambiguation.year_suffix?),
+            ),
+            Variable::Number(NumberVariable::PageFirst) => {
+                match self.field("page-first").and_then(Value::to_str) {
+                    Some(value) => value,
+                    None => {
+                        let page = self.field("page")?.to_str()?;
+                        Cow::Owned(crate::number::page_first(&page)?.to_string())
+                    }
+                }
+            }
+            Variable::Date(_) | Variable::Name(_) => return None,
+            _ => self.field(&variable.to_string())?.to_str()?,
+        };
+
+        (!value.trim().is_empty()).then_some(value)
+    }
+
+    /// The value of a variable for a `cs:text` element.
+    fn text_variable(&self, variable: Variable, form: LongShortForm) -> Option<String> {
+        if form == LongShortForm::Short
+            && let Variable::Standard(_) = variable
+            && !self.suppressed.contains(&variable)
+        {
+            let key = format!("{variable}-short");
+            let short = self.field(&key).and_then(Value::to_str);
+            if let Some(short) = short.filter(|s| !s.trim().is_empty()) {
+                return Some(short.into_owned());
+            }
+        }
+
+        let value = self.variable(variable)?;
+        Some(match variable {
+            Variable::Page(PageVariable::Page) => self.format_page(&value),
+            _ => value.into_owned(),
+        })
+    }
+
+    /// The names in a name variable.
+    fn names(&self, variable: NameVariable) -> Option<&'a [NameValue]> {
+        if self.suppressed.contains(&Variable::Name(variable)) {
+            return None;
+        }
+
+        match self.field(&variable.to_string())? {
+            Value::Names(names) if !names.is_empty() => Some(names),
+            _ => None,
+        }
+    }
+
+    /// The value of a date variable.
+    fn date(&self, variable: DateVariable) -> Option<&'a DateValue> {
+        if self.suppressed.contains(&Variable::Date(variable)) {
+            return None;
+        }
+
+        // Dates without a usable year or literal count as missing.
+        match self.field(&variable.to_string())? {
+            Value::Date(date)
+                if FixedDateRange::try_from(date.clone()).is_ok()
+                    || date.literal().is_some() =>
+            {
+                Some(date)
+            }
+            _ => None,
+        }
+    }
+
+    /// Format the page ranges of a value according to the style's page range
+    /// format.
+    fn format_page(&self, value: &str) -> String {
+        let delimiter = self
+            .locale()
+            .localize(OtherTerm::PageRangeDelimiter.into(), TermForm::Long, false, None)
+            .unwrap_or("–");
+        let format = self.processor.style.settings.page_range_format;
+        crate::number::format_pages(value, format, delimiter)
+    }
+
+    /// Format a number variable in the given form.
+    fn format_number(&self, value: &str, form: NumberForm, term: Term) -> String {
+        let locale = self.locale();
+        crate::number::format(value, form, &locale.ordinals(), locale.gender(term))
+    }
+}
+
+impl CitationContext for Renderer<'_> {
+    fn kind(&self) -> Kind {
+        self.field("type")
+            .and_then(Value::to_str)
+            .and_then(|t| Kind::from_str(&t).ok())
+            .unwrap_or(Kind::Document)
+    }
+
+    fn has_variable(&self, variable: Variable) -> bool {
+        match variable {
+            Variable::Name(name) => self.names(name).is_some(),
+            Variable::Date(date) => self.date(date).is_some(),
+            _ => self.variable(variable).is_some(),
+        }
+    }
+
+    fn variable_text(&self, variable: Variable) -> Option<Cow<'_, str>> {
+        self.variable(variable)
+    }
+
+    fn is_uncertain_date(&self, variable: DateVariable) -> bool {
+        self.date(variable).is_some_and(DateValue::circa)
+    }
+
+    fn locator(&self) -> Option<Locator> {
+        self.cite.locator.and_then(|(locator, _)| locator)
+    }
+
+    fn position(&self) -> Option<CitePosition> {
+        self.cite.position
+    }
+
+    fn is_near_note(&self) -> bool {
+        self.cite.near_note
+    }
+
+    fn is_disambiguating(&self) -> bool {
+        self.cite.disambiguation.condition
+    }
+}
+
+/// The rendering attributes shared by most elements.
+#[derive(Default)]
+struct Attributes {
+    formatting: Formatting,
+    affixes: Affixes,
+    display: Option<Display>,
+    quotes: bool,
+    strip_periods: bool,
+    text_case: Option<TextCase>,
+}
+
+/// Whether the nodes render no text.
+fn is_empty(nodes: &[Node]) -> bool {
+    nodes.iter().all(Node::is_empty)
+}
+
+/// Concatenate the non-empty parts with a delimiter.
+fn join(parts: Vec<Vec<Node>>, delimiter: Option<&str>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for part in parts {
+        if is_empty(&part) {
+            continue;
+        }
+
+        if !nodes.is_empty()
+            && let Some(delimiter) = delimiter
+        {
+            nodes.push(Node::Text(delimiter.to_string()));
+        }
+        nodes.extend(part);
+    }
+    nodes
+}
+
+/// Call a function for each text in the nodes, in order.
+fn for_each_text(nodes: &mut [Node], f: &mut impl FnMut(&mut String)) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => f(text),
+            _ => for_each_text(node.children_mut().unwrap(), f),
+        }
+    }
+}
+
+/// Convert field content with CSL-JSON markup to nodes.
+fn rich_nodes(text: &str) -> Vec<Node> {
+    fn convert(nodes: Vec<RichNode>) -> Vec<Node> {
+        nodes
+            .into_iter()
+            .map(|node| match node {
+                RichNode::Text(text) => Node::Text(text),
+                RichNode::Formatted(formatting, children) => {
+                    Node::Formatted(formatting, convert(children))
+                }
+                RichNode::NoCase(children) => Node::NoCase(convert(children)),
+            })
+            .collect()
+    }
+
+    convert(RichText::parse(text).0)
+}
+
+/// Change the case of the text in the nodes, leaving `nocase` spans as they
+/// are.
+fn apply_text_case(nodes: &mut [Node], case: TextCase) {
+    fn collect<'n>(
+        nodes: &'n mut [Node],
+        protected: bool,
+        pieces: &mut Vec<(&'n mut String, bool)>,
+    ) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => pieces.push((text, protected)),
+                Node::NoCase(children) => collect(children, true, pieces),
+                _ => collect(node.children_mut().unwrap(), protected, pieces),
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+    collect(nodes, false, &mut pieces);
+    case::change_case_pieces(&mut pieces, case);
+}
+
+/// Whether a number variable holds more than one number.
+fn is_plural(variable: Variable, value: &str) -> bool {
+    if variable.is_number_of_variable() {
+        return value.trim().parse::<i64>().is_ok_and(|n| n > 1);
+    }
+
+    crate::number::is_numeric(value) && value.contains([',', '&', '-', '–'])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LocaleFile;
+    use std::fmt::Write;
+    use std::fs;
+    use std::path::Path;
+
+    /// Parse the sections of a test case in the format of the citeproc test
+    /// suite, like `>>===== MODE =====>> citation <<===== MODE =====<<`.
+    fn sections(source: &str) -> HashMap<&str, &str> {
+        let mut sections = HashMap::new();
+        let mut rest = source;
+        while let Some(start) = rest.find(">>=====") {
+            let header = &rest[start + 7..];
+            let Some(end) = header.find("=====>>") else { break };
+            let name = header[..end].trim();
+            let body = &header[end + 7..];
+            let closing = format!("<<===== {name} =====<<");
+            let Some(close) = body.find(&closing) else { break };
+            sections.insert(name, body[..close].trim_matches(['\r', '\n']));
+            rest = &body[close + closing.len()..];
+        }
+        sections
+    }
+
+    /// Run a test case and describe the mismatch if it fails.
+    fn run(source: &str, locales: &[LocaleFile]) -> Result<(), String> {
+        let sections = sections(source);
+        let section = |name| sections.get(name).copied().ok_or(format!("no {name}"));
+
+        let style =
+            IndependentStyle::from_xml(section("CSL")?).map_err(|e| e.to_string())?;
+        let items: Vec<Item> =
+            serde_json::from_str(section("INPUT")?).map_err(|e| e.to_string())?;
+        let mut processor =
+            Processor::new(&style, style.locale_resolver(None, locales), &items);
+        let citations = citations(&sections, &items)?;
+        processor.disambiguate(&citations).map_err(|e| e.to_string())?;
+
+        let actual = match section("MODE")?.trim() {
+            "citation" => render_citations(&processor, &sections, &citations)?,
+            "bibliography" => {
+                let entries =
+                    processor.render_bibliography().map_err(|e| e.to_string())?;
+                let mut html = String::from("<div class=\"csl-bib-body\">\n");
+                for entry in entries {
+                    html.push_str("  <div class=\"csl-entry\">");
+                    html.push_str(&entry.output.to_html());
+                    html.push_str("</div>\n");
+                }
+                html.push_str("</div>");
+                html
+            }
+            mode => return Err(format!("unsupported mode {mode}")),
+        };
+
+        let expected = section("RESULT")?;
+        if actual == normalize_result(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected:\n{expected}\n\nactual:\n{actual}"))
+        }
+    }
+
+    /// The citation clusters of a test case. Without explicit clusters, all
+    /// items are cited in one cluster.
+    fn citations(
+        sections: &HashMap<&str, &str>,
+        items: &[Item],
+    ) -> Result<Vec<json::Citation>, String> {
+        Ok(if let Some(citations) = sections.get("CITATIONS") {
+            let clusters: Vec<(json::Citation, serde_json::Value, serde_json::Value)> =
+                serde_json::from_str(citations).map_err(|e| e.to_string())?;
+            clusters.into_iter().map(|(c, _, _)| c).collect()
+        } else if let Some(clusters) = sections.get("CITATION-ITEMS") {
+            let clusters: Vec<Vec<json::CitationItem>> =
+                serde_json::from_str(clusters).map_err(|e| e.to_string())?;
+            clusters
+                .into_iter()
+                .enumerate()
+                .map(|(i, items)| citation(i, items))
+                .collect()
+        } else {
+            let ids = items
+                .iter()
+                .filter_map(|i| i.id())
+                .map(|id| format!(r#"{{"id":"{id}"}}"#));
+            let cluster = format!("[{}]", ids.collect::<Vec<_>>().join(","));
+            vec![citation(0, serde_json::from_str(&cluster).unwrap())]
+        })
+    }
+
+    fn render_citations(
+        processor: &Processor,
+        sections: &HashMap<&str, &str>,
+        citations: &[json::Citation],
+    ) -> Result<String, String> {
+        let outputs = processor.render_citations(citations).map_err(|e| e.to_string())?;
+        let lines: Vec<String> = if sections.contains_key("CITATIONS") {
+            outputs
+                .iter()
+                .enumerate()
+                .map(|(i, o)| format!("[{i}] {}", o.to_html()))
+                .collect()
+        } else {
+            outputs.iter().map(Output::to_html).collect()
+        };
+
+        Ok(lines.join("\n"))
+    }
+
+    fn citation(i: usize, citation_items: Vec<json::CitationItem>) -> json::Citation {
+        json::Citation {
+            citation_id: format!("CITATION-{}", i + 1),
+            citation_items,
+            properties: json::CitationProperties { note_index: Some(i as u32 + 1) },
+        }
+    }
+
+    /// Drop the markers for updated citations from `CITATIONS` results.
+    fn normalize_result(expected: &str) -> String {
+        expected
+            .lines()
+            .map(|line| {
+                line.strip_prefix("..")
+                    .or_else(|| line.strip_prefix(">>"))
+                    .unwrap_or(line)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn locales() -> Vec<LocaleFile> {
+        fs::read_dir("tests/locales")
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .map(|xml| LocaleFile::from_xml(&xml).unwrap())
+            .collect()
+    }
+
+    /// A fixture that failed, with its name and the reason.
+    struct Failure {
+        name: String,
+        error: String,
+    }
+
+    /// Run all test cases in a folder and return how many there are and
+    /// which ones failed.
+    fn run_folder(folder: &Path, locales: &[LocaleFile]) -> (usize, Vec<Failure>) {
+        let mut total = 0;
+        let mut failures = Vec::new();
+        let mut paths: Vec<_> =
+            fs::read_dir(folder).unwrap().map(|e| e.unwrap().path()).collect();
+        paths.sort();
+
+        for path in paths {
+            if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                continue;
+            }
+
+            total += 1;
+            let source = fs::read_to_string(&path).unwrap();
+            if let Err(error) = run(&source, locales) {
+                let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+                failures.push(Failure { name, error });
+            }
+        }
+
+        (total, failures)
+    }
+
+    /// Describe the failures for an assertion message.
+    fn report<'f>(
+        failures: impl IntoIterator<Item = &'f Failure>,
+        total: usize,
+    ) -> String {
+        let mut out = String::new();
+        let mut count = 0;
+        for failure in failures {
+            count += 1;
+            writeln!(out, "{} failed:\n{}\n", failure.name, failure.error).unwrap();
+        }
+        writeln!(out, "{count} out of {total} fixtures failed").unwrap();
+        out
+    }
+
+    #[test]
+    fn processor_fixtures() {
+        let (total, failures) = run_folder(Path::new("tests/processor"), &locales());
+        assert!(total > 0);
+        assert!(failures.is_empty(), "{}", report(&failures, total));
+    }
+
+    /// Run the fixtures of the citeproc test suite. Check out
+    /// https://github.com/citation-style-language/test-suite into a sibling
+    /// folder to run this.
+    ///
+    /// The fixtures listed in `tests/citeproc-passing.txt` must pass. Add a
+    /// fixture to the list once it passes.
+    #[test]
+    fn citeproc_test_suite() {
+        let folder = Path::new("../test-suite/processor-tests/humans");
+        let (total, failures) = run_folder(folder, &locales());
+        let passing = fs::read_to_string("tests/citeproc-passing.txt").unwrap();
+        let passing: Vec<_> = passing
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && !name.starts_with('#'))
+            .collect();
+
+        for name in &passing {
+            let path = folder.join(format!("{name}.txt"));
+            assert!(path.exists(), "{name} is not part of the test suite");
+        }
+
+        let regressions: Vec<_> = failures
+            .iter()
+            .filter(|f| passing.contains(&f.name.as_str()))
+            .collect();
+        assert!(regressions.is_empty(), "{}", report(regressions, total));
+    }
+}
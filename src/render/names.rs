@@ -0,0 +1,768 @@
+//! Formatting of names and rendering of `cs:names` elements.
+
+use std::fmt;
+
+use super::{
+    Attributes, AuthorSubstitution, GivenName, Node, Output, RenderError, Renderer,
+    is_empty, join,
+};
+use crate::json::NameValue;
+use crate::taxonomy::{NameVariable, OtherTerm, Term};
+use crate::{
+    CitePosition, DelimiterBehavior, DemoteNonDroppingParticle, IndependentStyleSettings,
+    InheritableNameOptions, LayoutRenderingElement, Name, NameAnd, NameAsSortOrder,
+    NameForm, NameLabelPosition, NameOptions, NamePart, Names, SortKey, Substitute,
+    TermForm, ToAffixes, ToFormatting,
+};
+
+/// Formats lists of names according to the options of a `cs:name` element.
+///
+/// The formatter does not look up any terms: The words for `and` and et al.
+/// have to be resolved by the caller.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct NameFormatter<'a> {
+    /// The options of the `cs:name` element.
+    pub options: NameOptions<'a>,
+    /// Whether to keep the hyphen when initializing compound given names.
+    pub initialize_with_hyphen: bool,
+    /// Whether non-dropping particles stay with the family name.
+    pub demote_non_dropping_particle: DemoteNonDroppingParticle,
+    /// Whether the names are formatted for a sort key. Sort keys demote
+    /// non-dropping particles unless the style never does.
+    pub sorting: bool,
+    /// The word or symbol between the last two names, if any.
+    pub and: Option<&'a str>,
+    /// The term after a truncated list of names. No term is added if this is
+    /// `None` or empty.
+    pub et_al: Option<&'a str>,
+}
+
+impl<'a> NameFormatter<'a> {
+    /// Create a formatter for the options with the name settings of a style.
+    pub fn new(options: NameOptions<'a>, settings: &IndependentStyleSettings) -> Self {
+        Self {
+            options,
+            initialize_with_hyphen: settings.initialize_with_hyphen,
+            demote_non_dropping_particle: settings.demote_non_dropping_particle,
+            sorting: false,
+            and: None,
+            et_al: None,
+        }
+    }
+
+    /// Format a list of names, truncating it if the et-al options ask for it.
+    /// Set `subsequent` if the list appears in a repeated citation of the
+    /// same item. Names that format to nothing are left out.
+    pub fn format(&self, names: &[NameValue], subsequent: bool) -> NameList {
+        let formatted: Vec<FormattedName> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| self.format_name(name, i))
+            .filter(|name| !name.pieces.is_empty())
+            .collect();
+        let len = formatted.len();
+        let first = NameOptions { et_al_use_last: false, ..self.options };
+        let shown: Vec<&FormattedName> = (0..len)
+            .filter(|&k| !first.is_suppressed(k, len, subsequent))
+            .map(|k| &formatted[k])
+            .collect();
+        let truncated = shown.len() < len;
+
+        let mut items = Vec::new();
+        for (k, &name) in shown.iter().enumerate() {
+            if k > 0 {
+                let last = k + 1 == shown.len() && !truncated;
+                let delimiter = match self.and {
+                    Some(and) if last => {
+                        let after = shown[k - 1].index;
+                        let precedes = precedes(
+                            self.options.delimiter_precedes_last,
+                            shown.len() > 2,
+                            self.is_inverted(&names[after], after),
+                        );
+                        let delimiter =
+                            if precedes { self.options.delimiter } else { " " };
+                        format!("{delimiter}{and} ")
+                    }
+                    _ => self.options.delimiter.to_string(),
+                };
+                items.push(NameListItem::Delimiter(delimiter));
+            }
+            items.push(NameListItem::Name(name.clone()));
+        }
+
+        let Some(last) = shown.last().map(|name| name.index) else {
+            return NameList { items };
+        };
+        if !truncated {
+            return NameList { items };
+        }
+
+        // The last name can only be shown if at least one name is left out
+        // between it and the truncated list.
+        if self.options.et_al_use_last && shown.len() + 2 <= len {
+            items.push(NameListItem::Delimiter(format!("{}… ", self.options.delimiter)));
+            items.push(NameListItem::Name(formatted[len - 1].clone()));
+        } else if let Some(et_al) = self.et_al.filter(|t| !t.is_empty()) {
+            let precedes = precedes(
+                self.options.delimiter_precedes_et_al,
+                shown.len() > 1,
+                self.is_inverted(&names[last], last),
+            );
+            let delimiter = if precedes { self.options.delimiter } else { " " };
+            items.push(NameListItem::Delimiter(delimiter.to_string()));
+            items.push(NameListItem::EtAl(et_al.to_string()));
+        }
+
+        NameList { items }
+    }
+
+    /// Format a single name. The `index` is the position of the name in its
+    /// list and decides whether the name is inverted.
+    pub fn format_name(&self, value: &NameValue, index: usize) -> FormattedName {
+        let mut name = FormattedName { index, pieces: Vec::new() };
+        let item = match value {
+            NameValue::Literal(literal) => {
+                name.push("", NamePiece::Family(literal.literal.clone()));
+                return name;
+            }
+            NameValue::Item(item) => item,
+        };
+
+        let particle = item.non_dropping_particle.as_deref();
+        let dropping = item.dropping_particle.as_deref();
+        let family = item.family.as_str();
+        let demote = match self.demote_non_dropping_particle {
+            DemoteNonDroppingParticle::Never => false,
+            DemoteNonDroppingParticle::SortOnly => self.sorting,
+            DemoteNonDroppingParticle::DisplayAndSort => true,
+        };
+
+        if self.options.form != NameForm::Long {
+            let family = if demote && self.sorting {
+                join_words([Some(family), particle])
+            } else {
+                join_words([particle, Some(family)])
+            };
+            name.push("", NamePiece::Family(family));
+            return name;
+        }
+
+        let given = item.given.as_deref().map(|g| self.initialize(g)).unwrap_or_default();
+        let given = Some(given.as_str());
+        let suffix = item.suffix.clone().filter(|s| !s.is_empty());
+
+        if self.is_inverted(value, index) {
+            let separator = self.options.sort_separator;
+            let (family, given) = if demote {
+                (family.to_string(), join_words([given, dropping, particle]))
+            } else {
+                (join_words([particle, Some(family)]), join_words([given, dropping]))
+            };
+            name.push("", NamePiece::Family(family));
+            name.push(separator, NamePiece::Given(given));
+            if let Some(suffix) = suffix {
+                name.push(separator, NamePiece::Other(suffix));
+            }
+        } else {
+            name.push("", NamePiece::Given(join_words([given, dropping])));
+            name.push(" ", NamePiece::Family(join_words([particle, Some(family)])));
+            if let Some(suffix) = suffix {
+                name.push(" ", NamePiece::Other(suffix));
+            }
+        }
+
+        name
+    }
+
+    /// Whether the name is displayed in sort order.
+    fn is_inverted(&self, value: &NameValue, index: usize) -> bool {
+        if !matches!(value, NameValue::Item(_)) || self.options.form != NameForm::Long {
+            return false;
+        }
+
+        match self.options.name_as_sort_order {
+            Some(NameAsSortOrder::All) => true,
+            Some(NameAsSortOrder::First) => index == 0,
+            None => false,
+        }
+    }
+
+    /// Initialize the given name if the options ask for it.
+    ///
+    /// If `initialize` is disabled, only names that already are initials are
+    /// normalized to use `initialize_with`.
+    fn initialize(&self, given: &str) -> String {
+        let Some(with) = self.options.initialize_with else { return given.to_string() };
+
+        let mut out = String::new();
+        for word in given.split(|c: char| c.is_whitespace() || c == '.') {
+            for (k, part) in word.split('-').enumerate() {
+                let Some(first) = part.chars().next() else { continue };
+                let hyphenated = k > 0;
+                if self.options.initialize || is_initial(part) {
+                    if hyphenated && self.initialize_with_hyphen {
+                        out.truncate(out.trim_end().len());
+                        out.push('-');
+                    }
+                    out.push(first);
+                    out.push_str(with);
+                } else {
+                    if hyphenated {
+                        out.truncate(out.trim_end().len());
+                        out.push('-');
+                    } else if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+                        out.push(' ');
+                    }
+                    out.push_str(part);
+                    out.push(' ');
+                }
+            }
+        }
+
+        out.truncate(out.trim_end().len());
+        out
+    }
+}
+
+/// A formatted list of names.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct NameList {
+    /// The names and the text between them in display order.
+    pub items: Vec<NameListItem>,
+}
+
+impl NameList {
+    /// The names in the list, including the last name of `et-al-use-last`.
+    pub fn names(&self) -> impl Iterator<Item = &FormattedName> {
+        self.items.iter().filter_map(|item| match item {
+            NameListItem::Name(name) => Some(name),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for NameList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                NameListItem::Name(name) => write!(f, "{name}")?,
+                NameListItem::Delimiter(text) | NameListItem::EtAl(text) => {
+                    f.write_str(text)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A part of a formatted name list.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum NameListItem {
+    /// A single name.
+    Name(FormattedName),
+    /// Text between names, including the `and` term and the ellipsis of
+    /// `et-al-use-last`.
+    Delimiter(String),
+    /// The et-al term.
+    EtAl(String),
+}
+
+/// A formatted name.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FormattedName {
+    /// The position of the name in its list.
+    pub index: usize,
+    /// The parts of the name in display order.
+    pub pieces: Vec<NamePiece>,
+}
+
+impl FormattedName {
+    /// Append a non-empty piece, preceded by the separator if it is not the
+    /// first one.
+    fn push(&mut self, separator: &str, piece: NamePiece) {
+        if piece.text().is_empty() {
+            return;
+        }
+
+        if !self.pieces.is_empty() && !separator.is_empty() {
+            self.pieces.push(NamePiece::Other(separator.to_string()));
+        }
+        self.pieces.push(piece);
+    }
+}
+
+impl fmt::Display for FormattedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pieces.iter().try_for_each(|piece| f.write_str(piece.text()))
+    }
+}
+
+/// A piece of a formatted name.
+///
+/// The pieces map to the `cs:name-part` elements that format them: The given
+/// part contains the given name and the dropping particle, and the family
+/// part contains the family name and any non-dropping particle that is not
+/// demoted. Literal names are a single family piece.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum NamePiece {
+    /// Text formatted by the given name part.
+    Given(String),
+    /// Text formatted by the family name part.
+    Family(String),
+    /// Other text like separators and suffixes.
+    Other(String),
+}
+
+impl NamePiece {
+    /// The text of the piece.
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Given(text) | Self::Family(text) | Self::Other(text) => text,
+        }
+    }
+}
+
+impl Renderer<'_> {
+    pub(super) fn render_names(
+        &mut self,
+        names: &Names,
+    ) -> Result<Vec<Node>, RenderError> {
+        let options = self.name_options.apply(&names.options());
+        let delimiter = options.names_delimiter.as_deref().unwrap_or_default();
+
+        // Only the first `cs:names` element of an entry is substituted.
+        let substitution = match self.rendered_names {
+            None => self.author_substitution.take(),
+            Some(_) => None,
+        };
+        let mut replaced = match substitution {
+            Some(AuthorSubstitution::Names(n)) => n,
+            _ => 0,
+        };
+
+        let mut parts = Vec::new();
+        let mut rendered = NameList::default();
+        for &variable in &names.variable {
+            let list = self.names(variable);
+            self.track(variable.into(), list.is_some());
+            if let Some(list) = list {
+                if !parts.is_empty() {
+                    rendered.items.push(NameListItem::Delimiter(delimiter.to_string()));
+                }
+                parts.push(self.render_name_list(
+                    names,
+                    variable,
+                    list,
+                    &options,
+                    &mut replaced,
+                    &mut rendered,
+                ));
+            }
+        }
+
+        if parts.is_empty() {
+            // The substitute takes the place of the names.
+            self.author_substitution = substitution;
+            return match names.substitute() {
+                Some(substitute) => self.render_substitute(names, substitute),
+                None => Ok(Vec::new()),
+            };
+        }
+
+        if self.rendered_names.is_none() {
+            self.rendered_names = Some(rendered);
+        }
+
+        let nodes = match substitution {
+            Some(AuthorSubstitution::List) => self.author_substitute(),
+            _ => join(parts, Some(delimiter)),
+        };
+        let nodes = self.decorate_names(names, nodes);
+        if self.first_names.is_none() {
+            self.first_names = Some(nodes.clone());
+        }
+
+        if std::mem::take(&mut self.suppress_author) {
+            return Ok(Vec::new());
+        }
+
+        Ok(nodes)
+    }
+
+    fn decorate_names(&self, names: &Names, nodes: Vec<Node>) -> Vec<Node> {
+        self.decorate(
+            nodes,
+            Attributes {
+                formatting: names.to_formatting(),
+                affixes: names.to_affixes(),
+                display: names.display,
+                ..Attributes::default()
+            },
+        )
+    }
+
+    /// Render the first child of `cs:substitute` that produces output and
+    /// suppress the variables it used in the rest of the cite.
+    ///
+    /// The output of the child replaces the names, so it is compared and
+    /// substituted like them by the `subsequent-author-substitute`.
+    fn render_substitute(
+        &mut self,
+        names: &Names,
+        substitute: &Substitute,
+    ) -> Result<Vec<Node>, RenderError> {
+        for child in &substitute.children {
+            let outer = self.recording.replace(Vec::new());
+            let pending = self.author_substitution.is_some();
+            let nodes = match child {
+                LayoutRenderingElement::Names(child) => {
+                    self.render_names(&names.from_names_substitute(child))
+                }
+                _ => self
+                    .render_element(child)
+                    .map(|nodes| self.decorate_names(names, nodes)),
+            };
+
+            let used = std::mem::replace(&mut self.recording, outer).unwrap_or_default();
+            let mut nodes = nodes?;
+            let substituted = pending && self.author_substitution.is_none();
+            if !is_empty(&nodes) || substituted {
+                if !matches!(child, LayoutRenderingElement::Names(_)) {
+                    if self.rendered_names.is_none() {
+                        self.rendered_names = Some(single_name(&nodes));
+                    }
+                    if self.author_substitution.take().is_some() {
+                        nodes = self.decorate_names(names, self.author_substitute());
+                    }
+                }
+
+                if let Some(recording) = &mut self.recording {
+                    recording.extend(used.iter().copied());
+                }
+                self.suppressed.extend(used);
+                return Ok(nodes);
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// The `subsequent-author-substitute` of the bibliography.
+    fn author_substitute(&self) -> Vec<Node> {
+        let substitute = self
+            .processor
+            .style
+            .bibliography
+            .as_ref()
+            .and_then(|bibliography| bibliography.subsequent_author_substitute.as_deref())
+            .unwrap_or_default();
+        vec![Node::Text(substitute.to_string())]
+    }
+
+    /// Render the names of a single variable with its label.
+    ///
+    /// The first `replaced` names are replaced with the
+    /// `subsequent-author-substitute` and the count is decreased accordingly.
+    /// The names are appended to `rendered` as they would be shown without
+    /// substitution.
+    fn render_name_list(
+        &self,
+        names: &Names,
+        variable: NameVariable,
+        list: &[NameValue],
+        inherited: &InheritableNameOptions,
+        replaced: &mut usize,
+        rendered: &mut NameList,
+    ) -> Vec<Node> {
+        let default = Name::default();
+        let name = names.name().unwrap_or(&default);
+        let et_al = names.et_al().copied().unwrap_or_default();
+
+        let mut options = name.options(inherited);
+        if let Some(key) = self.sort_key {
+            // Sort keys use names in sort order and can show more or fewer.
+            options.name_as_sort_order = Some(NameAsSortOrder::All);
+            if let SortKey::MacroName {
+                names_min, names_use_first, names_use_last, ..
+            } = key
+            {
+                if let Some(min) = *names_min {
+                    options.et_al_min = Some(min);
+                    options.et_al_subsequent_min = Some(min);
+                }
+                if let Some(use_first) = *names_use_first {
+                    options.et_al_use_first = Some(use_first);
+                    options.et_al_subsequent_use_first = Some(use_first);
+                }
+                if let Some(use_last) = *names_use_last {
+                    options.et_al_use_last = use_last;
+                }
+            }
+        }
+
+        if let Some(n) = self.cite.disambiguation.names {
+            // Disambiguation can show more names than the style would.
+            let use_first =
+                [&mut options.et_al_use_first, &mut options.et_al_subsequent_use_first];
+            for use_first in use_first.into_iter().flatten() {
+                *use_first = (*use_first).max(n as u32);
+            }
+        }
+
+        let mut formatter = NameFormatter::new(options, &self.processor.style.settings);
+        formatter.sorting = self.sort_key.is_some();
+        formatter.and = formatter.options.and.map(|and| self.and_term(and));
+        formatter.et_al =
+            self.locale().localize(et_al.term.into(), TermForm::Long, false, None);
+
+        let subsequent = self.cite.position.is_some_and(|p| p != CitePosition::First);
+        let formatted = formatter.format(list, subsequent);
+
+        let mut nodes = if formatter.options.form == NameForm::Count {
+            vec![Node::Text(formatted.names().count().to_string())]
+        } else {
+            let nodes = formatted
+                .items
+                .iter()
+                .flat_map(|item| match item {
+                    NameListItem::Name(formatted) => {
+                        let index = formatted.index;
+                        let formatted =
+                            match self.cite.disambiguation.given_name(variable, index) {
+                                Some(given) => {
+                                    let mut expanded = formatter;
+                                    expanded.options.form = NameForm::Long;
+                                    if given == GivenName::Full {
+                                        expanded.options.initialize_with = None;
+                                    }
+                                    expanded.format_name(&list[index], index)
+                                }
+                                None => formatted.clone(),
+                            };
+
+                        let nodes = if *replaced > 0 {
+                            *replaced -= 1;
+                            self.author_substitute()
+                        } else {
+                            self.render_name(name, &formatted)
+                        };
+                        rendered.items.push(NameListItem::Name(formatted));
+                        nodes
+                    }
+                    NameListItem::Delimiter(text) => {
+                        rendered.items.push(item.clone());
+                        vec![Node::Text(text.clone())]
+                    }
+                    NameListItem::EtAl(text) => {
+                        rendered.items.push(item.clone());
+                        self.decorate(
+                            vec![Node::Text(text.clone())],
+                            Attributes {
+                                formatting: et_al.formatting,
+                                ..Attributes::default()
+                            },
+                        )
+                    }
+                })
+                .collect();
+
+            self.decorate(
+                nodes,
+                Attributes {
+                    formatting: name.to_formatting(),
+                    affixes: name.to_affixes(),
+                    ..Attributes::default()
+                },
+            )
+        };
+
+        if let Some((label, position)) = names.label() {
+            let term = Term::from(variable);
+            let label = self.render_term_label(term, list.len() > 1, label);
+            match position {
+                NameLabelPosition::BeforeName => {
+                    nodes.splice(0..0, label);
+                }
+                NameLabelPosition::AfterName => nodes.extend(label),
+            }
+        }
+
+        nodes
+    }
+
+    /// Render a formatted name with the formatting of its name parts.
+    fn render_name(&self, name: &Name, formatted: &FormattedName) -> Vec<Node> {
+        let part = |part: Option<&NamePart>, text: &str| {
+            let node = vec![Node::Text(text.to_string())];
+            let Some(part) = part else { return node };
+            self.decorate(
+                node,
+                Attributes {
+                    formatting: part.to_formatting(),
+                    affixes: part.to_affixes(),
+                    text_case: part.text_case,
+                    ..Attributes::default()
+                },
+            )
+        };
+
+        formatted
+            .pieces
+            .iter()
+            .flat_map(|piece| match piece {
+                NamePiece::Given(text) => part(name.name_part_given(), text),
+                NamePiece::Family(text) => part(name.name_part_family(), text),
+                NamePiece::Other(text) => vec![Node::Text(text.clone())],
+            })
+            .collect()
+    }
+
+    fn and_term(&self, and: NameAnd) -> &str {
+        match and {
+            NameAnd::Text => self
+                .locale()
+                .localize(Term::Other(OtherTerm::And), TermForm::Long, false, None)
+                .unwrap_or("and"),
+            NameAnd::Symbol => "&",
+        }
+    }
+}
+
+/// A name list with a single name for output that takes the place of names.
+fn single_name(nodes: &[Node]) -> NameList {
+    let text = Output(nodes.to_vec()).to_plain();
+    NameList {
+        items: vec![NameListItem::Name(FormattedName {
+            index: 0,
+            pieces: vec![NamePiece::Other(text)],
+        })],
+    }
+}
+
+/// Whether a delimiter precedes the last name or et al.
+fn precedes(behavior: DelimiterBehavior, contextual: bool, after_inverted: bool) -> bool {
+    match behavior {
+        DelimiterBehavior::Contextual => contextual,
+        DelimiterBehavior::AfterInvertedName => after_inverted,
+        DelimiterBehavior::Always => true,
+        DelimiterBehavior::Never => false,
+    }
+}
+
+/// Whether a part of a given name already is an initial.
+fn is_initial(part: &str) -> bool {
+    let mut chars = part.chars();
+    chars.next().is_some_and(char::is_uppercase) && chars.next().is_none()
+}
+
+/// Join the non-empty words with spaces. Particles that end in an apostrophe
+/// or hyphen attach to the following word.
+fn join_words<'s>(words: impl IntoIterator<Item = Option<&'s str>>) -> String {
+    let mut out = String::new();
+    for word in words.into_iter().flatten().filter(|w| !w.is_empty()) {
+        if !out.is_empty() && !out.ends_with(['\'', '’', '-']) {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json::{LiteralName, NameItem};
+
+    fn person(given: &str, family: &str) -> NameValue {
+        NameValue::Item(NameItem {
+            family: family.into(),
+            given: Some(given.into()),
+            non_dropping_particle: None,
+            dropping_particle: None,
+            suffix: None,
+        })
+    }
+
+    #[test]
+    fn formatter() {
+        let name = Name::default();
+        let inherited = InheritableNameOptions::default();
+        let mut formatter = NameFormatter {
+            options: name.options(&inherited),
+            initialize_with_hyphen: true,
+            demote_non_dropping_particle: DemoteNonDroppingParticle::DisplayAndSort,
+            sorting: false,
+            and: Some("and"),
+            et_al: Some("et al."),
+        };
+
+        let fontaine = NameValue::Item(NameItem {
+            family: "Fontaine".into(),
+            given: Some("Jean-Luc".into()),
+            non_dropping_particle: Some("La".into()),
+            dropping_particle: Some("de".into()),
+            suffix: Some("III".into()),
+        });
+        let names = [
+            fontaine,
+            person("John Ronald Reuel", "Tolkien"),
+            NameValue::Literal(LiteralName { literal: "UNESCO".into() }),
+            person("Anne", "Roe"),
+        ];
+
+        let format =
+            |f: &NameFormatter, subsequent| f.format(&names, subsequent).to_string();
+        assert_eq!(
+            format(&formatter, false),
+            "Jean-Luc de La Fontaine III, John Ronald Reuel Tolkien, UNESCO, and Anne Roe"
+        );
+
+        let empty = || person("", "");
+        let format_empty =
+            |names: &[NameValue]| formatter.format(names, false).to_string();
+        assert_eq!(format_empty(&[empty(), empty()]), "");
+        assert_eq!(format_empty(&[empty(), person("Anne", "Roe"), empty()]), "Anne Roe");
+
+        formatter.options.initialize_with = Some(". ");
+        formatter.options.name_as_sort_order = Some(NameAsSortOrder::First);
+        formatter.options.delimiter_precedes_last = DelimiterBehavior::Never;
+        assert_eq!(
+            format(&formatter, false),
+            "Fontaine, J.-L. de La, III, J. R. R. Tolkien, UNESCO and A. Roe"
+        );
+
+        formatter.initialize_with_hyphen = false;
+        formatter.demote_non_dropping_particle = DemoteNonDroppingParticle::Never;
+        formatter.options.initialize_with = Some(".");
+        formatter.options.et_al_min = Some(3);
+        formatter.options.et_al_use_first = Some(1);
+        formatter.options.delimiter_precedes_et_al = DelimiterBehavior::AfterInvertedName;
+        assert_eq!(format(&formatter, false), "La Fontaine, J.L. de, III, et al.");
+
+        formatter.options.delimiter_precedes_et_al = DelimiterBehavior::Contextual;
+        formatter.options.et_al_use_last = true;
+        formatter.options.et_al_subsequent_use_first = Some(3);
+        formatter.options.form = NameForm::Short;
+        assert_eq!(format(&formatter, false), "La Fontaine, … Roe");
+        assert_eq!(format(&formatter, true), "La Fontaine, Tolkien, UNESCO, et al.");
+
+        formatter.sorting = true;
+        formatter.demote_non_dropping_particle = DemoteNonDroppingParticle::SortOnly;
+        assert_eq!(format(&formatter, true), "Fontaine La, Tolkien, UNESCO, et al.");
+        formatter.sorting = false;
+
+        formatter.options.initialize = false;
+        formatter.options.form = NameForm::Long;
+        let name = formatter.format_name(&person("Jane R", "Doe"), 1);
+        assert_eq!(name.to_string(), "Jane R. Doe");
+        assert_eq!(
+            name.pieces,
+            [
+                NamePiece::Given("Jane R.".into()),
+                NamePiece::Other(" ".into()),
+                NamePiece::Family("Doe".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Numbering of the items for the `citation-number` variable.
+
+use super::{Processor, RenderError, disambiguate};
+use crate::SortKey;
+use crate::json;
+use crate::taxonomy::{NumberVariable, Variable};
+
+impl Processor<'_> {
+    /// Number the items cited in a sequence of citation clusters.
+    ///
+    /// The numbers follow the sort of the bibliography. If the bibliography
+    /// is not sorted, or sorted by the citation number itself, the items are
+    /// numbered in the order they are first cited, followed by the items that
+    /// are not cited in the order they were passed in. The numbers apply to
+    /// all citations and bibliography entries rendered afterwards.
+    pub fn assign_numbers(
+        &mut self,
+        citations: &[json::Citation],
+    ) -> Result<(), RenderError> {
+        let mut cited = vec![false; self.items.len()];
+        let mut order = Vec::with_capacity(self.items.len());
+        for citation_item in citations.iter().flat_map(|c| &c.citation_items) {
+            let index = self.index(&citation_item.id)?;
+            if !std::mem::replace(&mut cited[index], true) {
+                order.push(index);
+            }
+        }
+        order.extend((0..self.items.len()).filter(|&i| !cited[i]));
+        self.cite_order = order;
+
+        // Sort keys and macros may use the numbers in the order of citation.
+        self.number(self.cite_order.clone());
+        if !self.sorts_by_number() {
+            let order = self.bibliography_order()?;
+            self.number(order);
+        }
+
+        Ok(())
+    }
+
+    /// The citation number of the item with the given ID.
+    pub fn citation_number(&self, id: &str) -> Option<usize> {
+        self.ids.get(id).map(|&i| self.numbers[i])
+    }
+
+    /// The year suffix of the item with the given ID, like "a" or "ab".
+    ///
+    /// Items only have a year suffix if [`Processor::disambiguate`] assigned
+    /// one to set their cites apart.
+    pub fn year_suffix(&self, id: &str) -> Option<String> {
+        self.disambiguation(id)?.year_suffix.map(disambiguate::year_suffix)
+    }
+
+    /// Number the items in the given order.
+    fn number(&mut self, order: Vec<usize>) {
+        for (k, i) in order.into_iter().enumerate() {
+            self.numbers[i] = k + 1;
+        }
+    }
+
+    /// Whether the bibliography is sorted by the citation number.
+    fn sorts_by_number(&self) -> bool {
+        let Some(bibliography) = &self.style.bibliography else { return false };
+        bibliography.sort.iter().flat_map(|sort| &sort.keys).any(|key| {
+            matches!(
+                key,
+                SortKey::Variable {
+                    variable: Variable::Number(NumberVariable::CitationNumber),
+                    ..
+                }
+            )
+        })
+    }
+}
@@ -0,0 +1,735 @@
+//! The formatted output of the processor.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    Display, FontStyle, FontVariant, FontWeight, Formatting, TextDecoration,
+    VerticalAlign,
+};
+
+/// A piece of rendered output.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Node {
+    /// Literal text.
+    Text(String),
+    /// Children with formatting applied.
+    Formatted(Formatting, Vec<Node>),
+    /// Children in quotation marks.
+    Quoted {
+        /// The opening quotation mark.
+        open: String,
+        /// The closing quotation mark.
+        close: String,
+        /// The quoted content.
+        children: Vec<Node>,
+    },
+    /// Children on their own layout level.
+    Display(Display, Vec<Node>),
+    /// Children whose case must not be changed, from a `nocase` span in rich
+    /// text.
+    NoCase(Vec<Node>),
+    /// Children that link to a URL.
+    Link {
+        /// The link target.
+        url: String,
+        /// The linked content.
+        children: Vec<Node>,
+    },
+}
+
+impl Node {
+    /// Whether the node renders no text.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Text(text) => text.is_empty(),
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children) => children.iter().all(Self::is_empty),
+            Self::Quoted { children, .. } | Self::Link { children, .. } => {
+                children.iter().all(Self::is_empty)
+            }
+        }
+    }
+
+    /// The children of the node, if it can have any.
+    pub fn children(&self) -> Option<&[Node]> {
+        match self {
+            Self::Text(_) => None,
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children)
+            | Self::Quoted { children, .. }
+            | Self::Link { children, .. } => Some(children),
+        }
+    }
+
+    /// The children of the node, if it can have any.
+    pub fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Self::Text(_) => None,
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children)
+            | Self::Quoted { children, .. }
+            | Self::Link { children, .. } => Some(children),
+        }
+    }
+
+    /// The first text of the node if no quotation mark precedes it.
+    fn first_text(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children)
+            | Self::Link { children, .. } => children.first_mut()?.first_text(),
+            Self::Quoted { .. } => None,
+        }
+    }
+
+    /// The last text of the node if no quotation mark follows it.
+    fn last_text(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children)
+            | Self::Link { children, .. } => children.last_mut()?.last_text(),
+            Self::Quoted { .. } => None,
+        }
+    }
+
+    /// The content of the quotation the node ends with.
+    fn last_quote(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Self::Text(_) => None,
+            Self::Formatted(_, children)
+            | Self::Display(_, children)
+            | Self::NoCase(children)
+            | Self::Link { children, .. } => children.last_mut()?.last_quote(),
+            Self::Quoted { children, .. } => Some(children),
+        }
+    }
+}
+
+/// The rendered output of a citation or bibliography entry.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct Output(pub Vec<Node>);
+
+impl Output {
+    /// Whether the output contains no text.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(Node::is_empty)
+    }
+
+    /// Render the output in a format.
+    pub fn render(&self, format: &impl OutputFormat) -> String {
+        let mut buf = String::new();
+        write_nodes(&mut buf, format, &self.0);
+        buf
+    }
+
+    /// Render the output as HTML in the same way as `citeproc-js`.
+    pub fn to_html(&self) -> String {
+        self.render(&Html)
+    }
+
+    /// Render the output as text without formatting.
+    pub fn to_plain(&self) -> String {
+        self.render(&PlainText)
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_plain())
+    }
+}
+
+/// A markup language that output can be rendered to.
+///
+/// Each method appends to the buffer. The content passed to the methods is
+/// already rendered in the format.
+pub trait OutputFormat {
+    /// Write text, escaping it as needed.
+    fn text(&self, buf: &mut String, text: &str);
+
+    /// Write content with formatting applied.
+    fn formatted(&self, buf: &mut String, formatting: &Formatting, content: &str);
+
+    /// Write content on its own layout level.
+    fn display(&self, buf: &mut String, display: Display, content: &str);
+
+    /// Write content that links to a URL.
+    fn link(&self, buf: &mut String, url: &str, content: &str);
+}
+
+fn write_nodes(buf: &mut String, format: &impl OutputFormat, nodes: &[Node]) {
+    let render = |children: &[Node]| {
+        let mut content = String::new();
+        write_nodes(&mut content, format, children);
+        content
+    };
+
+    for node in nodes {
+        match node {
+            Node::Text(text) => format.text(buf, text),
+            Node::Formatted(formatting, children) => {
+                format.formatted(buf, formatting, &render(children))
+            }
+            Node::Quoted { open, close, children } => {
+                format.text(buf, open);
+                write_nodes(buf, format, children);
+                format.text(buf, close);
+            }
+            Node::Display(display, children) => {
+                format.display(buf, *display, &render(children))
+            }
+            Node::NoCase(children) => write_nodes(buf, format, children),
+            Node::Link { url, children } => format.link(buf, url, &render(children)),
+        }
+    }
+}
+
+/// Text without any formatting.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PlainText;
+
+impl OutputFormat for PlainText {
+    fn text(&self, buf: &mut String, text: &str) {
+        buf.push_str(text);
+    }
+
+    fn formatted(&self, buf: &mut String, _: &Formatting, content: &str) {
+        buf.push_str(content);
+    }
+
+    fn display(&self, buf: &mut String, _: Display, content: &str) {
+        buf.push_str(content);
+    }
+
+    fn link(&self, buf: &mut String, _: &str, content: &str) {
+        buf.push_str(content);
+    }
+}
+
+/// HTML that matches the output of `citeproc-js`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Html;
+
+impl OutputFormat for Html {
+    fn text(&self, buf: &mut String, text: &str) {
+        for c in text.chars() {
+            match c {
+                '&' => buf.push_str("&#38;"),
+                '<' => buf.push_str("&#60;"),
+                '>' => buf.push_str("&#62;"),
+                c => buf.push(c),
+            }
+        }
+    }
+
+    fn formatted(&self, buf: &mut String, formatting: &Formatting, content: &str) {
+        const SPAN: &str = "</span>";
+        // The tags from the innermost to the outermost.
+        let mut tags = Vec::new();
+
+        match formatting.font_style {
+            Some(FontStyle::Italic) => tags.push(("<i>", "</i>")),
+            Some(FontStyle::Normal) => {
+                tags.push(("<span style=\"font-style:normal;\">", SPAN))
+            }
+            None => {}
+        }
+
+        match formatting.font_variant {
+            Some(FontVariant::SmallCaps) => {
+                tags.push(("<span style=\"font-variant:small-caps;\">", SPAN))
+            }
+            Some(FontVariant::Normal) => {
+                tags.push(("<span style=\"font-variant:normal;\">", SPAN))
+            }
+            None => {}
+        }
+
+        match formatting.font_weight {
+            Some(FontWeight::Bold) => tags.push(("<b>", "</b>")),
+            Some(FontWeight::Normal) => {
+                tags.push(("<span style=\"font-weight:normal;\">", SPAN))
+            }
+            Some(FontWeight::Light) | None => {}
+        }
+
+        match formatting.text_decoration {
+            Some(TextDecoration::Underline) => {
+                tags.push(("<span style=\"text-decoration:underline;\">", SPAN))
+            }
+            Some(TextDecoration::None) => {
+                tags.push(("<span style=\"text-decoration:none;\">", SPAN))
+            }
+            None => {}
+        }
+
+        match formatting.vertical_align {
+            Some(VerticalAlign::Sup) => tags.push(("<sup>", "</sup>")),
+            Some(VerticalAlign::Sub) => tags.push(("<sub>", "</sub>")),
+            Some(VerticalAlign::Baseline) => {
+                tags.push(("<span style=\"baseline\">", SPAN))
+            }
+            Some(VerticalAlign::None) | None => {}
+        }
+
+        for (open, _) in tags.iter().rev() {
+            buf.push_str(open);
+        }
+        buf.push_str(content);
+        for (_, close) in &tags {
+            buf.push_str(close);
+        }
+    }
+
+    fn display(&self, buf: &mut String, display: Display, content: &str) {
+        let class = match display {
+            Display::Block => "csl-block",
+            Display::LeftMargin => "csl-left-margin",
+            Display::RightInline => "csl-right-inline",
+            Display::Indent => "csl-indent",
+        };
+        buf.push_str("<div class=\"");
+        buf.push_str(class);
+        buf.push_str("\">");
+        buf.push_str(content);
+        buf.push_str("</div>");
+    }
+
+    fn link(&self, buf: &mut String, url: &str, content: &str) {
+        buf.push_str("<a href=\"");
+        for c in url.chars() {
+            match c {
+                '&' => buf.push_str("&#38;"),
+                '"' => buf.push_str("&#34;"),
+                c => buf.push(c),
+            }
+        }
+        buf.push_str("\">");
+        buf.push_str(content);
+        buf.push_str("</a>");
+    }
+}
+
+/// Rich Text Format as produced by `citeproc-js`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Rtf;
+
+impl OutputFormat for Rtf {
+    fn text(&self, buf: &mut String, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\\' | '{' | '}' => {
+                    buf.push('\\');
+                    buf.push(c);
+                }
+                '\t' => buf.push_str("\\tab{}"),
+                '\n' => buf.push_str("\\line{}"),
+                c if c.is_ascii() => buf.push(c),
+                c => {
+                    // RTF uses signed 16-bit code units.
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        write!(buf, "\\uc0\\u{}{{}}", *unit as i16).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    fn formatted(&self, buf: &mut String, formatting: &Formatting, content: &str) {
+        let mut controls = Vec::new();
+        match formatting.font_style {
+            Some(FontStyle::Italic) => controls.push("\\i"),
+            Some(FontStyle::Normal) => controls.push("\\i0"),
+            None => {}
+        }
+        match formatting.font_variant {
+            Some(FontVariant::SmallCaps) => controls.push("\\scaps"),
+            Some(FontVariant::Normal) => controls.push("\\scaps0"),
+            None => {}
+        }
+        match formatting.font_weight {
+            Some(FontWeight::Bold) => controls.push("\\b"),
+            Some(FontWeight::Normal) => controls.push("\\b0"),
+            Some(FontWeight::Light) | None => {}
+        }
+        match formatting.text_decoration {
+            Some(TextDecoration::Underline) => controls.push("\\ul"),
+            Some(TextDecoration::None) => controls.push("\\ul0"),
+            None => {}
+        }
+        match formatting.vertical_align {
+            Some(VerticalAlign::Sup) => controls.push("\\super"),
+            Some(VerticalAlign::Sub) => controls.push("\\sub"),
+            Some(VerticalAlign::Baseline) => controls.push("\\nosupersub"),
+            Some(VerticalAlign::None) | None => {}
+        }
+
+        buf.push('{');
+        if !controls.is_empty() {
+            for control in controls {
+                buf.push_str(control);
+            }
+            buf.push(' ');
+        }
+        buf.push_str(content);
+        buf.push('}');
+    }
+
+    fn display(&self, buf: &mut String, display: Display, content: &str) {
+        match display {
+            Display::Block => write!(buf, "\\line {content}\\line "),
+            Display::LeftMargin => write!(buf, "{content}\\tab "),
+            Display::RightInline => write!(buf, "{content}"),
+            Display::Indent => write!(buf, "\\line \\tab {content}\\line "),
+        }
+        .unwrap();
+    }
+
+    fn link(&self, buf: &mut String, url: &str, content: &str) {
+        let mut escaped = String::new();
+        self.text(&mut escaped, url);
+        write!(
+            buf,
+            "{{\\field{{\\*\\fldinst{{HYPERLINK \"{escaped}\"}}}}{{\\fldrslt {content}}}}}"
+        )
+        .unwrap();
+    }
+}
+
+/// LaTeX markup.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Latex;
+
+impl OutputFormat for Latex {
+    fn text(&self, buf: &mut String, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\\' => buf.push_str("\\textbackslash{}"),
+                '~' => buf.push_str("\\textasciitilde{}"),
+                '^' => buf.push_str("\\textasciicircum{}"),
+                '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                    buf.push('\\');
+                    buf.push(c);
+                }
+                c => buf.push(c),
+            }
+        }
+    }
+
+    fn formatted(&self, buf: &mut String, formatting: &Formatting, content: &str) {
+        let mut commands = Vec::new();
+        match formatting.font_style {
+            Some(FontStyle::Italic) => commands.push("\\textit"),
+            Some(FontStyle::Normal) => commands.push("\\textup"),
+            None => {}
+        }
+        match formatting.font_variant {
+            Some(FontVariant::SmallCaps) => commands.push("\\textsc"),
+            Some(FontVariant::Normal) => commands.push("\\textulc"),
+            None => {}
+        }
+        match formatting.font_weight {
+            Some(FontWeight::Bold) => commands.push("\\textbf"),
+            Some(FontWeight::Normal) | Some(FontWeight::Light) => {
+                commands.push("\\textmd")
+            }
+            None => {}
+        }
+        if formatting.text_decoration == Some(TextDecoration::Underline) {
+            commands.push("\\underline");
+        }
+        match formatting.vertical_align {
+            Some(VerticalAlign::Sup) => commands.push("\\textsuperscript"),
+            Some(VerticalAlign::Sub) => commands.push("\\textsubscript"),
+            Some(VerticalAlign::Baseline) | Some(VerticalAlign::None) | None => {}
+        }
+
+        for command in &commands {
+            buf.push_str(command);
+            buf.push('{');
+        }
+        buf.push_str(content);
+        buf.extend(commands.iter().map(|_| '}'));
+    }
+
+    fn display(&self, buf: &mut String, display: Display, content: &str) {
+        match display {
+            Display::Block => write!(buf, "\\par{{}}{content}\\par{{}}"),
+            Display::LeftMargin => write!(buf, "{content}\\quad{{}}"),
+            Display::RightInline => write!(buf, "{content}"),
+            Display::Indent => write!(buf, "\\par{{}}\\quad{{}}{content}\\par{{}}"),
+        }
+        .unwrap();
+    }
+
+    fn link(&self, buf: &mut String, url: &str, content: &str) {
+        buf.push_str("\\href{");
+        for c in url.chars() {
+            if matches!(c, '\\' | '{' | '}' | '#' | '%') {
+                buf.push('\\');
+            }
+            buf.push(c);
+        }
+        buf.push_str("}{");
+        buf.push_str(content);
+        buf.push('}');
+    }
+}
+
+/// Typst markup.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Typst;
+
+impl OutputFormat for Typst {
+    fn text(&self, buf: &mut String, text: &str) {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            // Headings and lists only start at the beginning of a line.
+            let line_start = || {
+                let line = buf.trim_end_matches([' ', '\t']);
+                line.is_empty() || line.ends_with('\n')
+            };
+            let escape = match c {
+                '\\' | '*' | '_' | '`' | '$' | '#' | '[' | ']' | '<' | '>' | '@'
+                | '~' | '/' | '"' | '\'' => true,
+                // Dashes and ellipses are shorthands.
+                '-' | '.' if chars.peek() == Some(&c) => true,
+                '=' | '-' | '+' => line_start(),
+                _ => false,
+            };
+            if escape {
+                buf.push('\\');
+            }
+            buf.push(c);
+        }
+    }
+
+    fn formatted(&self, buf: &mut String, formatting: &Formatting, content: &str) {
+        let mut functions = Vec::new();
+        match formatting.font_style {
+            Some(FontStyle::Italic) => functions.push("text(style: \"italic\")"),
+            Some(FontStyle::Normal) => functions.push("text(style: \"normal\")"),
+            None => {}
+        }
+        match formatting.font_weight {
+            Some(FontWeight::Bold) => functions.push("text(weight: \"bold\")"),
+            Some(FontWeight::Normal) => functions.push("text(weight: \"regular\")"),
+            Some(FontWeight::Light) => functions.push("text(weight: \"light\")"),
+            None => {}
+        }
+        if formatting.font_variant == Some(FontVariant::SmallCaps) {
+            functions.push("smallcaps");
+        }
+        if formatting.text_decoration == Some(TextDecoration::Underline) {
+            functions.push("underline");
+        }
+        match formatting.vertical_align {
+            Some(VerticalAlign::Sup) => functions.push("super"),
+            Some(VerticalAlign::Sub) => functions.push("sub"),
+            Some(VerticalAlign::Baseline) | Some(VerticalAlign::None) | None => {}
+        }
+
+        for function in &functions {
+            buf.push('#');
+            buf.push_str(function);
+            buf.push('[');
+        }
+        buf.push_str(content);
+        buf.extend(functions.iter().map(|_| ']'));
+    }
+
+    fn display(&self, buf: &mut String, display: Display, content: &str) {
+        match display {
+            Display::Block => write!(buf, "#block[{content}]"),
+            Display::LeftMargin | Display::RightInline => write!(buf, "#box[{content}]"),
+            Display::Indent => write!(buf, "#block(inset: (left: 2em))[{content}]"),
+        }
+        .unwrap();
+    }
+
+    fn link(&self, buf: &mut String, url: &str, content: &str) {
+        buf.push_str("#link(\"");
+        for c in url.chars() {
+            if matches!(c, '\\' | '"') {
+                buf.push('\\');
+            }
+            buf.push(c);
+        }
+        buf.push_str("\")[");
+        buf.push_str(content);
+        buf.push(']');
+    }
+}
+
+/// Clean up rendered nodes: drop empty nodes, merge adjacent text, remove
+/// duplicate periods, and move periods and commas into preceding quotation
+/// marks if `punctuation_in_quote` is set.
+pub(super) fn normalize(nodes: &mut Vec<Node>, punctuation_in_quote: bool) {
+    for node in nodes.iter_mut() {
+        if let Some(children) = node.children_mut() {
+            normalize(children, punctuation_in_quote);
+        }
+    }
+
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes.drain(..) {
+        if node.is_empty() {
+            continue;
+        }
+
+        if let (Some(Node::Text(prev)), Node::Text(next)) = (merged.last_mut(), &node) {
+            push_text(prev, next);
+        } else {
+            merged.push(node);
+        }
+    }
+
+    for i in 1..merged.len() {
+        let (head, tail) = merged.split_at_mut(i);
+        let (prev, next) = (&mut head[i - 1], &mut tail[0]);
+
+        if punctuation_in_quote
+            && let Some(quote) = prev.last_quote()
+            && let Some(text) = next.first_text()
+            && text.starts_with(['.', ','])
+        {
+            let punct = text.remove(0);
+            match quote.last_mut().and_then(Node::last_text) {
+                Some(last) => push_text(last, &punct.to_string()),
+                None => quote.push(Node::Text(punct.to_string())),
+            }
+        }
+
+        if let Some(text) = next.first_text()
+            && text.starts_with('.')
+            && prev.last_text().is_some_and(|t| ends_with_stop(t))
+        {
+            text.remove(0);
+        }
+    }
+
+    merged.retain(|n| !n.is_empty());
+    *nodes = merged;
+}
+
+/// Append text, dropping a leading period if the text already ends with
+/// terminal punctuation.
+fn push_text(buf: &mut String, text: &str) {
+    match text.strip_prefix('.') {
+        Some(rest) if ends_with_stop(buf) => buf.push_str(rest),
+        _ => buf.push_str(text),
+    }
+}
+
+fn ends_with_stop(text: &str) -> bool {
+    text.ends_with(['.', '?', '!'])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(s: &str) -> Node {
+        Node::Text(s.to_string())
+    }
+
+    #[test]
+    fn punctuation() {
+        let mut nodes = vec![
+            Node::Formatted(
+                Formatting {
+                    font_style: Some(FontStyle::Italic),
+                    ..Default::default()
+                },
+                vec![text("Why?")],
+            ),
+            text(". "),
+            Node::Quoted {
+                open: "“".into(),
+                close: "”".into(),
+                children: vec![text("A & B")],
+            },
+            text(", "),
+            text("end."),
+            text("."),
+        ];
+
+        normalize(&mut nodes, true);
+        let output = Output(nodes);
+        assert_eq!(output.to_plain(), "Why? “A & B,” end.");
+        assert_eq!(output.to_html(), "<i>Why?</i> “A &#38; B,” end.");
+    }
+
+    #[test]
+    fn formats() {
+        let output = Output(vec![
+            Node::Display(Display::LeftMargin, vec![text("[1]")]),
+            Node::Formatted(
+                Formatting {
+                    font_style: Some(FontStyle::Italic),
+                    font_weight: Some(FontWeight::Bold),
+                    ..Default::default()
+                },
+                vec![text("A & B_{é}")],
+            ),
+            text(" "),
+            Node::Link {
+                url: "https://example.com/#x".into(),
+                children: vec![text("link")],
+            },
+        ]);
+
+        assert_eq!(output.to_plain(), "[1]A & B_{é} link");
+        assert_eq!(
+            output.to_html(),
+            "<div class=\"csl-left-margin\">[1]</div><b><i>A &#38; B_{é}</i></b> \
+             <a href=\"https://example.com/#x\">link</a>"
+        );
+        assert_eq!(
+            output.render(&Rtf),
+            "[1]\\tab {\\i\\b A & B_\\{\\uc0\\u233{}\\}} \
+             {\\field{\\*\\fldinst{HYPERLINK \"https://example.com/#x\"}}{\\fldrslt link}}"
+        );
+
+        let light = Output(vec![Node::Formatted(
+            Formatting {
+                font_weight: Some(FontWeight::Light),
+                ..Default::default()
+            },
+            vec![text("light")],
+        )]);
+        assert_eq!(light.render(&Rtf), "{light}");
+
+        let upright = Output(vec![Node::Formatted(
+            Formatting {
+                font_variant: Some(FontVariant::Normal),
+                ..Default::default()
+            },
+            vec![text("upright")],
+        )]);
+        assert_eq!(upright.render(&Latex), "\\textulc{upright}");
+
+        let markup = Output(vec![text("= \"It's\" -- 1--2... - +\n+ x=y-z")]);
+        assert_eq!(
+            markup.render(&Typst),
+            "\\= \\\"It\\'s\\\" \\-- 1\\--2\\.\\.. - +\n\\+ x=y-z"
+        );
+        assert_eq!(
+            output.render(&Latex),
+            "[1]\\quad{}\\textit{\\textbf{A \\& B\\_\\{é\\}}} \
+             \\href{https://example.com/\\#x}{link}"
+        );
+        assert_eq!(
+            output.render(&Typst),
+            "#box[\\[1\\]]#text(style: \"italic\")[#text(weight: \"bold\")[A & B\\_{é}]] \
+             #link(\"https://example.com/#x\")[link]"
+        );
+    }
+}
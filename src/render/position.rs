@@ -0,0 +1,173 @@
+//! Tracking of cite positions across the citations of a document.
+
+use std::collections::HashMap;
+
+use crate::CitePosition;
+use crate::json;
+
+/// Where a cite is in the sequence of cites of its item.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TrackedPosition {
+    /// The position of the cite.
+    pub position: CitePosition,
+    /// Whether the item was cited before in the same note or in one of the
+    /// `near-note-distance` notes before it.
+    pub near_note: bool,
+    /// The note of the first cite of the item, if the item was cited before
+    /// in a note.
+    pub first_reference_note_number: Option<u32>,
+}
+
+/// Computes the positions of cites from the citations of a document.
+///
+/// Feed the citations to [`PositionTracker::track`] in document order. The
+/// note index of a citation's properties decides which cites are near each
+/// other. Citations without a note index are never near other ones.
+///
+/// ```
+/// use citationberg::CitePosition;
+/// use citationberg::render::PositionTracker;
+///
+/// let mut tracker = PositionTracker::new(5);
+/// let citation = |id: &str, locator: &str| {
+///     serde_json::from_str(&format!(
+///         r#"{{"citationID": "c", "citationItems": [{{"id": "{id}", "locator": "{locator}"}}], "properties": {{"noteIndex": 1}}}}"#
+///     )).unwrap()
+/// };
+///
+/// let first = tracker.track(&citation("a", "1"));
+/// let ibid = tracker.track(&citation("a", "2"));
+/// assert_eq!(first[0].position, CitePosition::First);
+/// assert_eq!(ibid[0].position, CitePosition::IbidWithLocator);
+/// assert_eq!(ibid[0].first_reference_note_number, Some(1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PositionTracker {
+    near_note_distance: u32,
+    /// The note of the first cite of each item, by ID.
+    first_notes: HashMap<String, Option<u32>>,
+    /// The note of the latest cite of each item that was cited in a note.
+    last_notes: HashMap<String, u32>,
+    /// The IDs and locators of the cites of the previous citation.
+    previous: Vec<(String, Option<Locator>)>,
+}
+
+/// The label and value of a locator.
+type Locator = (String, String);
+
+impl PositionTracker {
+    /// Create a tracker for a style's `near-note-distance`.
+    pub fn new(near_note_distance: u32) -> Self {
+        Self { near_note_distance, ..Self::default() }
+    }
+
+    /// Compute the positions of the cites of the next citation.
+    ///
+    /// A cite is ibid if it directly follows a cite of the same item: Either
+    /// the previous cite in the same citation, or, for the first cite of a
+    /// citation, the only cite of the previous citation. It is ibid with
+    /// locator if its locator differs from that of the preceding cite, and
+    /// only subsequent if it has no locator but the preceding cite has one.
+    pub fn track(&mut self, citation: &json::Citation) -> Vec<TrackedPosition> {
+        let note = citation.properties.note_index;
+        let mut current: Vec<(String, Option<Locator>)> = Vec::new();
+        let mut positions = Vec::with_capacity(citation.citation_items.len());
+
+        for item in &citation.citation_items {
+            let locator =
+                item.locator.as_ref().filter(|l| !l.trim().is_empty()).map(|l| {
+                    (item.label.clone().unwrap_or_else(|| "page".into()), l.clone())
+                });
+
+            let preceding = match current.last() {
+                Some(preceding) => Some(preceding),
+                None if self.previous.len() == 1 => self.previous.first(),
+                None => None,
+            };
+
+            let first_note = self.first_notes.get(&item.id).copied();
+            let position = match (first_note, preceding) {
+                (None, _) => CitePosition::First,
+                (Some(_), Some((id, preceding))) if *id == item.id => {
+                    match (&locator, preceding) {
+                        (None, None) => CitePosition::Ibid,
+                        (Some(a), Some(b)) if a == b => CitePosition::Ibid,
+                        (Some(_), _) => CitePosition::IbidWithLocator,
+                        (None, Some(_)) => CitePosition::Subsequent,
+                    }
+                }
+                (Some(_), _) => CitePosition::Subsequent,
+            };
+
+            let near_note = match (note, self.last_notes.get(&item.id)) {
+                (Some(note), Some(&last)) => {
+                    note >= last && note - last <= self.near_note_distance
+                }
+                _ => false,
+            };
+
+            positions.push(TrackedPosition {
+                position,
+                near_note,
+                first_reference_note_number: first_note.flatten(),
+            });
+
+            self.first_notes.entry(item.id.clone()).or_insert(note);
+            if let Some(note) = note {
+                self.last_notes.insert(item.id.clone(), note);
+            }
+            current.push((item.id.clone(), locator));
+        }
+
+        self.previous = current;
+        positions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn citation(note: u32, items: &str) -> json::Citation {
+        serde_json::from_str(&format!(
+            r#"{{"citationID": "c{note}", "citationItems": {items}, "properties": {{"noteIndex": {note}}}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn positions() {
+        use CitePosition::*;
+
+        let mut tracker = PositionTracker::new(2);
+        let mut track = |note, items| {
+            tracker
+                .track(&citation(note, items))
+                .into_iter()
+                .map(|p| (p.position, p.near_note, p.first_reference_note_number))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            track(1, r#"[{"id": "a"}, {"id": "a", "locator": "5"}, {"id": "b"}]"#),
+            [
+                (First, false, None),
+                (IbidWithLocator, true, Some(1)),
+                (First, false, None)
+            ]
+        );
+
+        // The previous citation has several cites, so this is not ibid.
+        assert_eq!(track(2, r#"[{"id": "b"}]"#), [(Subsequent, true, Some(1))]);
+        assert_eq!(
+            track(3, r#"[{"id": "b", "locator": "4"}]"#),
+            [(IbidWithLocator, true, Some(1))]
+        );
+        assert_eq!(track(4, r#"[{"id": "b"}]"#), [(Subsequent, true, Some(1))]);
+        assert_eq!(track(5, r#"[{"id": "b"}]"#), [(Ibid, true, Some(1))]);
+        assert_eq!(
+            track(8, r#"[{"id": "a", "locator": "5"}]"#),
+            [(Subsequent, false, Some(1))]
+        );
+    }
+}
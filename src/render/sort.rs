@@ -0,0 +1,378 @@
+//! Sorting of cites and bibliography entries.
+
+use std::cmp::Ordering;
+
+use super::{Cite, NameFormatter, Output, Processor, RenderError, Renderer, rich_nodes};
+use crate::json::{FixedDate, FixedDateRange, NameValue};
+use crate::number::NumberPart;
+use crate::taxonomy::Variable;
+use crate::{
+    InheritableNameOptions, Name, NameAsSortOrder, NameForm, Sort, SortDirection, SortKey,
+};
+
+/// Compares text in the alphabetical order of a language.
+///
+/// Letters with diacritics sort like their base letters, numbers in the text
+/// sort by their value, and leading articles like "The" are ignored. Ties are
+/// broken by diacritics and then by case, with lowercase letters first. The
+/// Scandinavian languages sort their additional letters after "z".
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use citationberg::render::Collator;
+///
+/// let collator = Collator::new("en-US");
+/// assert_eq!(collator.compare("The Zebra", "Apple"), Ordering::Greater);
+/// assert_eq!(collator.compare("Émile", "Eric"), Ordering::Less);
+/// assert_eq!(collator.compare("Vol. 10", "Vol. 9"), Ordering::Greater);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Collator {
+    /// Articles that are ignored at the start of a text.
+    articles: &'static [&'static str],
+    /// Letters that sort after "z", in order.
+    extra_letters: &'static [char],
+}
+
+impl Collator {
+    /// Create a collator for a language tag like `de-DE`.
+    pub fn new(language: &str) -> Self {
+        let language = language.split(['-', '_']).next().unwrap_or_default();
+        let articles: &[&str] = match language.to_ascii_lowercase().as_str() {
+            "en" => &["the", "a", "an"],
+            "de" => &["der", "die", "das", "ein", "eine"],
+            "fr" => &["le", "la", "les", "l'", "l’", "un", "une"],
+            "es" => &["el", "la", "los", "las", "un", "una"],
+            "it" => &["il", "lo", "la", "i", "gli", "le", "l'", "l’", "un", "uno", "una"],
+            "pt" => &["o", "a", "os", "as", "um", "uma"],
+            "nl" => &["de", "het", "een"],
+            _ => &[],
+        };
+
+        let extra_letters: &[char] = match language.to_ascii_lowercase().as_str() {
+            "sv" | "fi" => &['å', 'ä', 'ö'],
+            "da" | "nb" | "nn" | "no" => &['æ', 'ø', 'å'],
+            _ => &[],
+        };
+
+        Self { articles, extra_letters }
+    }
+
+    /// Compare two texts.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.compare_stripped(self.strip_article(a), self.strip_article(b))
+    }
+
+    /// Compare two texts without ignoring articles.
+    fn compare_stripped(&self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (self.weights(a), self.weights(b));
+        a.primary
+            .cmp(&b.primary)
+            .then_with(|| a.accents.cmp(&b.accents))
+            .then_with(|| a.uppercase.cmp(&b.uppercase))
+    }
+
+    /// Remove a leading article that is followed by more text.
+    fn strip_article<'t>(&self, text: &'t str) -> &'t str {
+        let trimmed = text.trim_start();
+        for article in self.articles {
+            let Some(start) = trimmed.get(..article.len()) else { continue };
+            if !start.eq_ignore_ascii_case(article) {
+                continue;
+            }
+
+            let rest = &trimmed[article.len()..];
+            let rest = if article.ends_with(['\'', '’']) {
+                rest
+            } else if rest.starts_with(char::is_whitespace) {
+                rest.trim_start()
+            } else {
+                continue;
+            };
+
+            if !rest.is_empty() {
+                return rest;
+            }
+        }
+
+        trimmed
+    }
+
+    /// The collation weights of a text.
+    fn weights(&self, text: &str) -> Weights {
+        let mut weights = Weights::default();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_digit() {
+                let mut digits = String::from(c);
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                let digits = digits.trim_start_matches('0').to_string();
+                weights.primary.push(Element::Number(digits.len(), digits));
+            } else if c.is_whitespace() {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                weights.primary.push(Element::Other(' '));
+            } else if c.is_alphabetic() {
+                let lower = c.to_lowercase().next().unwrap_or(c);
+                weights.uppercase.push(c.is_uppercase());
+                if let Some(k) = self.extra_letters.iter().position(|&l| l == lower) {
+                    weights.primary.push(Element::Letter('z' as u32 + 1 + k as u32));
+                    weights.accents.push(false);
+                } else if let Some(base) = base_letters(lower) {
+                    weights
+                        .primary
+                        .extend(base.chars().map(|b| Element::Letter(b as u32)));
+                    weights.accents.push(true);
+                } else {
+                    weights.primary.push(Element::Letter(lower as u32));
+                    weights.accents.push(false);
+                }
+            } else {
+                weights.primary.push(Element::Other(c));
+            }
+        }
+        weights
+    }
+}
+
+/// The weights of a text at the three levels of collation.
+#[derive(Default)]
+struct Weights {
+    primary: Vec<Element>,
+    accents: Vec<bool>,
+    uppercase: Vec<bool>,
+}
+
+/// A unit of text for the primary collation level. Spaces and punctuation
+/// sort before numbers and numbers before letters.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+enum Element {
+    Other(char),
+    /// The number of significant digits and the digits.
+    Number(usize, String),
+    Letter(u32),
+}
+
+/// The base letters of a lowercase Latin letter with diacritics or a
+/// ligature.
+fn base_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+/// The value of a sort key for a cite.
+#[derive(Debug, Clone)]
+pub(super) enum SortValue {
+    /// Text like a title or the output of a macro.
+    Text(String),
+    /// Names in sort order, compared one by one.
+    Names(Vec<String>),
+    /// The first number of a number variable.
+    Number(i64),
+    /// A date as a zero-padded string.
+    Date(String),
+}
+
+impl SortValue {
+    fn compare(&self, other: &Self, collator: &Collator) -> Ordering {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.cmp(b),
+            (Self::Date(a), Self::Date(b)) => a.cmp(b),
+            (Self::Names(a), Self::Names(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| collator.compare_stripped(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(a.len().cmp(&b.len())),
+            _ => collator.compare(&self.text(), &other.text()),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Self::Text(text) | Self::Date(text) => text.clone(),
+            Self::Names(names) => names.join(", "),
+            Self::Number(number) => number.to_string(),
+        }
+    }
+}
+
+impl Processor<'_> {
+    /// The collator for the language of the locale.
+    pub(super) fn collator(&self) -> Collator {
+        Collator::new(self.locale.lang().map_or("en-US", |lang| lang.0.as_str()))
+    }
+
+    /// The values of the sort keys for a cite. Empty values are `None`.
+    pub(super) fn sort_values(
+        &self,
+        cite: &Cite,
+        sort: &Sort,
+        name_options: &InheritableNameOptions,
+    ) -> Result<Vec<Option<SortValue>>, RenderError> {
+        sort.keys
+            .iter()
+            .map(|key| {
+                let mut renderer = Renderer::new(self, cite, name_options);
+                renderer.sort_key = Some(key);
+                renderer.sort_value(key)
+            })
+            .collect()
+    }
+
+    /// Compare the sort values of two cites key by key. Empty values come
+    /// last, no matter in which direction a key sorts.
+    pub(super) fn compare_sort_values(
+        &self,
+        collator: &Collator,
+        sort: &Sort,
+        a: &[Option<SortValue>],
+        b: &[Option<SortValue>],
+    ) -> Ordering {
+        for ((key, a), b) in sort.keys.iter().zip(a).zip(b) {
+            let ordering = match (a, b) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => match key.sort_direction() {
+                    SortDirection::Ascending => a.compare(b, collator),
+                    SortDirection::Descending => a.compare(b, collator).reverse(),
+                },
+            };
+
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+impl Renderer<'_> {
+    /// The value of a sort key for the cite.
+    fn sort_value(&mut self, key: &SortKey) -> Result<Option<SortValue>, RenderError> {
+        let variable = match key {
+            SortKey::Variable { variable, .. } => *variable,
+            SortKey::MacroName { name, .. } => {
+                let text = Output(self.render_macro(name)?).to_plain();
+                return Ok((!text.trim().is_empty()).then_some(SortValue::Text(text)));
+            }
+        };
+
+        Ok(match variable {
+            Variable::Name(variable) => self
+                .names(variable)
+                .map(|names| SortValue::Names(self.sort_names(names))),
+            Variable::Date(variable) => self
+                .date(variable)
+                .and_then(|date| FixedDateRange::try_from(date.clone()).ok())
+                .map(|range| SortValue::Date(date_key(range, true, true))),
+            Variable::Number(_) | Variable::Page(_) => {
+                self.variable(variable).map(|value| {
+                    let first = crate::number::parse(&value).and_then(|parts| {
+                        parts.into_iter().find_map(|part| match part {
+                            NumberPart::Number(n, _) => Some(i64::from(n)),
+                            _ => None,
+                        })
+                    });
+                    match first {
+                        Some(n) => SortValue::Number(n),
+                        None => SortValue::Text(value.into_owned()),
+                    }
+                })
+            }
+            Variable::Standard(_) => self
+                .variable(variable)
+                .map(|value| SortValue::Text(Output(rich_nodes(&value)).to_plain())),
+        })
+    }
+
+    /// Format all names of a list in their long form and in sort order.
+    fn sort_names(&self, names: &[NameValue]) -> Vec<String> {
+        let default = Name::default();
+        let mut options = default.options(self.name_options);
+        options.form = NameForm::Long;
+        options.name_as_sort_order = Some(NameAsSortOrder::All);
+        options.et_al_min = None;
+        options.et_al_subsequent_min = None;
+        options.et_al_use_last = false;
+
+        let mut formatter = NameFormatter::new(options, &self.processor.style.settings);
+        formatter.sorting = true;
+        formatter
+            .format(names, false)
+            .names()
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+/// A date or date range as a string of zero-padded numbers that sorts
+/// chronologically. The month and day can be left out.
+pub(super) fn date_key(range: FixedDateRange, month: bool, day: bool) -> String {
+    let fixed = |date: FixedDate| {
+        let m = date.month.filter(|_| month).map_or(0, |m| m + 1);
+        let d = date.day.filter(|_| month && day).map_or(0, |d| d + 1);
+        format!("{:05}{m:02}{d:02}", i32::from(date.year) + 50_000)
+    };
+
+    let mut key = fixed(range.start);
+    if let Some(end) = range.end {
+        key.push_str(&fixed(end));
+    }
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collation() {
+        let en = Collator::new("en-US");
+        let mut words = ["zebra", "The Apple", "Émile", "Eric", "emile", "10", "9", "an"];
+        words.sort_by(|a, b| en.compare(a, b));
+        assert_eq!(
+            words,
+            ["9", "10", "an", "The Apple", "emile", "Émile", "Eric", "zebra"]
+        );
+
+        assert_eq!(en.compare("Straße", "Strasse"), Ordering::Greater);
+        assert_eq!(en.compare("Doe, John", "Doeb, Al"), Ordering::Less);
+
+        let sv = Collator::new("sv");
+        assert_eq!(sv.compare("Åsa", "Zorn"), Ordering::Greater);
+        assert_eq!(en.compare("Åsa", "Zorn"), Ordering::Less);
+
+        let fr = Collator::new("fr-FR");
+        assert_eq!(fr.compare("L’Étranger", "La Peste"), Ordering::Less);
+    }
+}
@@ -0,0 +1,203 @@
+//! Substitution of repeated names in a bibliography.
+
+use super::names::{FormattedName, NameList, NameListItem, NamePiece};
+use crate::SubsequentAuthorSubstituteRule;
+
+/// How the first name list of a bibliography entry is replaced with the
+/// `subsequent-author-substitute` of the bibliography.
+///
+/// This is computed by [`substitute_authors`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AuthorSubstitution {
+    /// The whole list, including the delimiters and the terms for "and" and
+    /// et al., is replaced once.
+    List,
+    /// Each of the first names of the list is replaced, keeping the text
+    /// between them.
+    Names(usize),
+}
+
+/// Decide which names of consecutive bibliography entries are replaced with
+/// the `subsequent-author-substitute` because they repeat the names of the
+/// entry before.
+///
+/// Pass the first rendered name list of each entry in bibliography order, or
+/// `None` for entries without one. If a `cs:names` element falls back to a
+/// `cs:substitute` child that renders no names, like a title, pass that
+/// output as a list with a single name. Entries are always compared to the
+/// names of the entry before as they were rendered, not as they were
+/// substituted.
+///
+/// ```
+/// use citationberg::SubsequentAuthorSubstituteRule;
+/// use citationberg::render::{
+///     AuthorSubstitution, FormattedName, NameList, NameListItem, NamePiece,
+///     substitute_authors,
+/// };
+///
+/// let list = |names: &[&str]| NameList {
+///     items: names
+///         .iter()
+///         .enumerate()
+///         .map(|(index, name)| {
+///             NameListItem::Name(FormattedName {
+///                 index,
+///                 pieces: vec![NamePiece::Family(name.to_string())],
+///             })
+///         })
+///         .collect(),
+/// };
+///
+/// let lists = [Some(list(&["Doe", "Roe"])), Some(list(&["Doe", "Smith"]))];
+/// let rule = SubsequentAuthorSubstituteRule::PartialEach;
+/// assert_eq!(
+///     substitute_authors(rule, &lists),
+///     [None, Some(AuthorSubstitution::Names(1))],
+/// );
+/// ```
+pub fn substitute_authors(
+    rule: SubsequentAuthorSubstituteRule,
+    lists: &[Option<NameList>],
+) -> Vec<Option<AuthorSubstitution>> {
+    let mut substitutions = vec![None];
+    for pair in lists.windows(2) {
+        let [Some(previous), Some(list)] = pair else {
+            substitutions.push(None);
+            continue;
+        };
+
+        let count = list.names().count();
+        let matching = previous
+            .names()
+            .zip(list.names())
+            .take_while(|(a, b)| a.to_string() == b.to_string())
+            .count();
+        let complete = previous.to_string() == list.to_string();
+
+        let substitution = match rule {
+            _ if count == 0 => None,
+            SubsequentAuthorSubstituteRule::CompleteAll if complete => {
+                Some(AuthorSubstitution::List)
+            }
+            SubsequentAuthorSubstituteRule::CompleteEach if complete => {
+                Some(AuthorSubstitution::Names(count))
+            }
+            SubsequentAuthorSubstituteRule::PartialEach if matching > 0 => {
+                Some(AuthorSubstitution::Names(matching))
+            }
+            SubsequentAuthorSubstituteRule::PartialFirst if matching > 0 => {
+                Some(AuthorSubstitution::Names(1))
+            }
+            _ => None,
+        };
+
+        substitutions.push(substitution);
+    }
+
+    substitutions.truncate(lists.len());
+    substitutions
+}
+
+impl NameList {
+    /// Replace the names of the list as decided by [`substitute_authors`].
+    ///
+    /// The list is emptied if the whole list is replaced with an empty
+    /// substitute.
+    pub fn substitute(&mut self, substitution: AuthorSubstitution, substitute: &str) {
+        let replacement = |index| {
+            NameListItem::Name(FormattedName {
+                index,
+                pieces: vec![NamePiece::Other(substitute.to_string())],
+            })
+        };
+
+        match substitution {
+            AuthorSubstitution::List => {
+                self.items.clear();
+                if !substitute.is_empty() {
+                    self.items.push(replacement(0));
+                }
+            }
+            AuthorSubstitution::Names(n) => {
+                let names = self
+                    .items
+                    .iter_mut()
+                    .filter(|item| matches!(item, NameListItem::Name(_)))
+                    .take(n);
+                for item in names {
+                    if let NameListItem::Name(name) = item {
+                        *item = replacement(name.index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use SubsequentAuthorSubstituteRule::*;
+
+    fn list(names: &[&str], et_al: bool) -> Option<NameList> {
+        let mut items = Vec::new();
+        for (index, name) in names.iter().enumerate() {
+            if index > 0 {
+                items.push(NameListItem::Delimiter(", ".into()));
+            }
+            items.push(NameListItem::Name(FormattedName {
+                index,
+                pieces: vec![NamePiece::Family(name.to_string())],
+            }));
+        }
+        if et_al {
+            items.push(NameListItem::EtAl(" et al.".into()));
+        }
+        Some(NameList { items })
+    }
+
+    #[test]
+    fn rules() {
+        let lists = [
+            list(&["Doe", "Roe"], false),
+            list(&["Doe", "Roe"], false),
+            list(&["Doe", "Roe"], true),
+            list(&["Doe", "Smith"], false),
+            None,
+            list(&["Doe", "Smith"], false),
+        ];
+
+        let names = |n| Some(AuthorSubstitution::Names(n));
+        assert_eq!(
+            substitute_authors(CompleteAll, &lists),
+            [None, Some(AuthorSubstitution::List), None, None, None, None]
+        );
+        assert_eq!(
+            substitute_authors(CompleteEach, &lists),
+            [None, names(2), None, None, None, None]
+        );
+        assert_eq!(
+            substitute_authors(PartialEach, &lists),
+            [None, names(2), names(2), names(1), None, None]
+        );
+        assert_eq!(
+            substitute_authors(PartialFirst, &lists),
+            [None, names(1), names(1), names(1), None, None]
+        );
+        assert!(substitute_authors(CompleteAll, &[]).is_empty());
+    }
+
+    #[test]
+    fn substitute() {
+        let mut partial = list(&["Doe", "Roe", "Smith"], false).unwrap();
+        partial.substitute(AuthorSubstitution::Names(2), "---");
+        assert_eq!(partial.to_string(), "---, ---, Smith");
+
+        let mut complete = list(&["Doe", "Roe"], true).unwrap();
+        complete.substitute(AuthorSubstitution::List, "---");
+        assert_eq!(complete.to_string(), "---");
+
+        complete.substitute(AuthorSubstitution::List, "");
+        assert!(complete.items.is_empty());
+    }
+}
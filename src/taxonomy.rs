@@ -571,6 +571,43 @@ impl fmt::Display for NameVariable {
     }
 }
 
+impl FromStr for NameVariable {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "author" => Ok(Self::Author),
+            "chair" => Ok(Self::Chair),
+            "collection-editor" => Ok(Self::CollectionEditor),
+            "compiler" => Ok(Self::Compiler),
+            "composer" => Ok(Self::Composer),
+            "container-author" => Ok(Self::ContainerAuthor),
+            "contributor" => Ok(Self::Contributor),
+            "curator" => Ok(Self::Curator),
+            "director" => Ok(Self::Director),
+            "editor" => Ok(Self::Editor),
+            "editorial-director" => Ok(Self::EditorialDirector),
+            "editor-translator" => Ok(Self::EditorTranslator),
+            "executive-producer" => Ok(Self::ExecutiveProducer),
+            "guest" => Ok(Self::Guest),
+            "host" => Ok(Self::Host),
+            "illustrator" => Ok(Self::Illustrator),
+            "interviewer" => Ok(Self::Interviewer),
+            "narrator" => Ok(Self::Narrator),
+            "organizer" => Ok(Self::Organizer),
+            "original-author" => Ok(Self::OriginalAuthor),
+            "performer" => Ok(Self::Performer),
+            "producer" => Ok(Self::Producer),
+            "recipient" => Ok(Self::Recipient),
+            "reviewed-author" => Ok(Self::ReviewedAuthor),
+            "script-writer" => Ok(Self::ScriptWriter),
+            "series-creator" => Ok(Self::SeriesCreator),
+            "translator" => Ok(Self::Translator),
+            _ => Err(()),
+        }
+    }
+}
+
 /// Localizable terms.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 #[serde(untagged)]
@@ -1024,6 +1061,7 @@ pub enum OtherTerm {
     OpenInnerQuote,
     CloseInnerQuote,
     PageRangeDelimiter,
+    CitationRangeDelimiter,
     Colon,
     Comma,
     Semicolon,
@@ -1231,6 +1269,7 @@ impl FromStr for OtherTerm {
             "open-inner-quote" => Ok(Self::OpenInnerQuote),
             "close-inner-quote" => Ok(Self::CloseInnerQuote),
             "page-range-delimiter" => Ok(Self::PageRangeDelimiter),
+            "citation-range-delimiter" => Ok(Self::CitationRangeDelimiter),
             "colon" => Ok(Self::Colon),
             "comma" => Ok(Self::Comma),
             "semicolon" => Ok(Self::Semicolon),
@@ -1325,6 +1364,7 @@ impl fmt::Display for OtherTerm {
             Self::OpenInnerQuote => write!(f, "open-inner-quote"),
             Self::CloseInnerQuote => write!(f, "close-inner-quote"),
             Self::PageRangeDelimiter => write!(f, "page-range-delimiter"),
+            Self::CitationRangeDelimiter => write!(f, "citation-range-delimiter"),
             Self::Colon => write!(f, "colon"),
             Self::Comma => write!(f, "comma"),
             Self::Semicolon => write!(f, "semicolon"),
@@ -70,3 +70,154 @@ pub fn deserialize_u32_option<'de, D: serde::Deserializer<'de>>(
     })
     .transpose()
 }
+
+/// An element encountered while scanning an XML document for an error
+/// location.
+struct ScannedElement {
+    name: String,
+    parent: Option<usize>,
+    start: usize,
+    attributes: Vec<(String, String)>,
+}
+
+/// Find the path to the last element that started before `offset` and the
+/// byte offset at which that element starts.
+///
+/// Scanning stops at the first XML syntax error, so this also works for
+/// malformed documents.
+pub fn element_path(
+    xml: &str,
+    offset: usize,
+    hints: &[&str],
+) -> (crate::ElementPath, usize) {
+    use quick_xml::events::{BytesStart, Event};
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut elements: Vec<ScannedElement> = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+
+    let mut open = |start: &BytesStart, pos: usize, stack: &[usize]| {
+        let name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+        let attributes = start
+            .attributes()
+            .filter_map(Result::ok)
+            .map(|attr| {
+                let key = attr.key.local_name();
+                let key = String::from_utf8_lossy(key.as_ref()).into_owned();
+                let value = attr.unescape_value().unwrap_or_default();
+                (key, value.into_owned())
+            })
+            .collect();
+        elements.push(ScannedElement {
+            name,
+            parent: stack.last().copied(),
+            start: pos,
+            attributes,
+        });
+        elements.len() - 1
+    };
+
+    loop {
+        let pos = reader.buffer_position() as usize;
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                let idx = open(&start, pos, &stack);
+                stack.push(idx);
+            }
+            Ok(Event::Empty(start)) => {
+                open(&start, pos, &stack);
+            }
+            Ok(Event::End(_)) => {
+                stack.pop();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+
+    let last = elements.iter().rposition(|e| e.start < offset.max(1));
+    let ancestors = std::iter::successors(last, |&idx| elements[idx].parent);
+
+    // Errors raised after the deserializer has read a whole element, like
+    // the validation of `cs:style`, name that element in the message. Look
+    // it up among the elements that enclose the error position so that the
+    // path points at the element that failed and not at another element
+    // with the same name.
+    let named = hints.iter().find_map(|hint| {
+        let name = hint.strip_prefix("cs:")?;
+        ancestors.clone().find(|&idx| elements[idx].name == name)
+    });
+
+    let Some(target) = named.or(last) else {
+        return (crate::ElementPath::default(), 0);
+    };
+
+    let mut segments = Vec::new();
+    let mut current = Some(target);
+    while let Some(idx) = current {
+        let element = &elements[idx];
+        let siblings = elements
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.parent == element.parent && e.name == element.name);
+        let count = siblings.clone().count();
+        let index =
+            (count > 1).then(|| siblings.take_while(|(i, _)| *i != idx).count() + 1);
+        segments.push(crate::PathSegment { name: element.name.clone(), index });
+        current = element.parent;
+    }
+    segments.reverse();
+
+    let attributes = &elements[target].attributes;
+    let attribute = hints
+        .iter()
+        .find_map(|hint| {
+            if let Some(name) = hint.strip_prefix('@') {
+                Some(name.to_string())
+            } else {
+                attributes.iter().find(|(_, v)| v == hint).map(|(k, _)| k.clone())
+            }
+        })
+        .or_else(|| invalid_attribute(attributes));
+
+    (crate::ElementPath { segments, attribute }, elements[target].start)
+}
+
+/// Find an attribute whose value is not a valid variable or term.
+///
+/// Errors of untagged enums like `cs:text`'s target do not contain the
+/// unknown value, so the attribute cannot be found through the hints.
+fn invalid_attribute(attributes: &[(String, String)]) -> Option<String> {
+    use serde::de::value::{Error, StrDeserializer};
+
+    let invalid = |value: &str, valid: fn(StrDeserializer<'_, Error>) -> bool| {
+        value
+            .split_whitespace()
+            .any(|word| !valid(StrDeserializer::new(word)))
+    };
+
+    attributes
+        .iter()
+        .find(|(key, value)| match key.as_str() {
+            "variable" => {
+                invalid(value, |de| crate::taxonomy::Variable::deserialize(de).is_ok())
+            }
+            "term" => invalid(value, |de| crate::taxonomy::Term::deserialize(de).is_ok()),
+            _ => false,
+        })
+        .map(|(key, _)| key.clone())
+}
+
+/// Convert a byte offset into a 1-based line and column pair. Columns are
+/// counted in characters.
+pub fn line_column(xml: &str, offset: usize) -> (usize, usize) {
+    let mut end = offset.min(xml.len());
+    while !xml.is_char_boundary(end) {
+        end -= 1;
+    }
+    let before = &xml[..end];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
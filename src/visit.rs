@@ -0,0 +1,600 @@
+//! Traversal of the element tree of a style.
+//!
+//! Implement [`Visit`] to inspect a style or [`VisitMut`] to rewrite it. Each
+//! method is called for one kind of node. By default, methods for nodes with
+//! children call the corresponding `walk_*` function to visit the children,
+//! and methods for leaf nodes do nothing. When overriding a method of a node
+//! with children, call the `walk_*` function yourself to continue the
+//! traversal below that node.
+//!
+//! ```
+//! use citationberg::visit::Visit;
+//! use citationberg::{IndependentStyle, Text, TextTarget};
+//!
+//! /// Counts the macro calls of a style.
+//! struct MacroCalls(usize);
+//!
+//! impl Visit<'_> for MacroCalls {
+//!     fn visit_text(&mut self, text: &Text) {
+//!         if matches!(text.target, TextTarget::Macro { .. }) {
+//!             self.0 += 1;
+//!         }
+//!     }
+//! }
+//!
+//! # fn count(style: &IndependentStyle) -> usize {
+//! let mut calls = MacroCalls(0);
+//! calls.visit_style(style);
+//! calls.0
+//! # }
+//! ```
+
+use crate::{
+    Bibliography, Choose, ChooseBranch, Citation, CslMacro, Date, DatePart, ElseBranch,
+    EtAl, Group, IndependentStyle, Label, Layout, LayoutRenderingElement, Locale, Name,
+    Names, NamesChild, Number, RenderingElement, Sort, SortKey, Substitute, Text,
+    VariablelessLabel,
+};
+
+/// Inspect the nodes of a style.
+///
+/// The lifetime allows visitors to keep references to the visited nodes.
+#[allow(unused_variables)]
+pub trait Visit<'a> {
+    /// Visit a style and its macros, locales, citation, and bibliography.
+    fn visit_style(&mut self, style: &'a IndependentStyle) {
+        walk_style(self, style)
+    }
+
+    /// Visit a macro definition.
+    fn visit_macro(&mut self, macro_: &'a CslMacro) {
+        walk_macro(self, macro_)
+    }
+
+    /// Visit the localization overrides of a style.
+    fn visit_locale(&mut self, locale: &'a Locale) {
+        walk_locale(self, locale)
+    }
+
+    /// Visit the citation element.
+    fn visit_citation(&mut self, citation: &'a Citation) {
+        walk_citation(self, citation)
+    }
+
+    /// Visit the bibliography element.
+    fn visit_bibliography(&mut self, bibliography: &'a Bibliography) {
+        walk_bibliography(self, bibliography)
+    }
+
+    /// Visit the sort element of a citation or bibliography.
+    fn visit_sort(&mut self, sort: &'a Sort) {
+        walk_sort(self, sort)
+    }
+
+    /// Visit a sort key.
+    fn visit_sort_key(&mut self, key: &'a SortKey) {}
+
+    /// Visit the layout of a citation or bibliography.
+    fn visit_layout(&mut self, layout: &'a Layout) {
+        walk_layout(self, layout)
+    }
+
+    /// Visit a layout or another rendering element.
+    fn visit_rendering_element(&mut self, element: &'a RenderingElement) {
+        walk_rendering_element(self, element)
+    }
+
+    /// Visit any rendering element within a layout.
+    fn visit_element(&mut self, element: &'a LayoutRenderingElement) {
+        walk_element(self, element)
+    }
+
+    /// Visit a text element.
+    fn visit_text(&mut self, text: &'a Text) {}
+
+    /// Visit a date element.
+    fn visit_date(&mut self, date: &'a Date) {
+        walk_date(self, date)
+    }
+
+    /// Visit a date part within a date element.
+    fn visit_date_part(&mut self, part: &'a DatePart) {}
+
+    /// Visit a number element.
+    fn visit_number(&mut self, number: &'a Number) {}
+
+    /// Visit a names element.
+    fn visit_names(&mut self, names: &'a Names) {
+        walk_names(self, names)
+    }
+
+    /// Visit a name element within a names element.
+    fn visit_name(&mut self, name: &'a Name) {}
+
+    /// Visit an et-al element within a names element.
+    fn visit_et_al(&mut self, et_al: &'a EtAl) {}
+
+    /// Visit a label element within a names element.
+    fn visit_names_label(&mut self, label: &'a VariablelessLabel) {}
+
+    /// Visit the substitute element of a names element.
+    fn visit_substitute(&mut self, substitute: &'a Substitute) {
+        walk_substitute(self, substitute)
+    }
+
+    /// Visit a label element.
+    fn visit_label(&mut self, label: &'a Label) {}
+
+    /// Visit a group element.
+    fn visit_group(&mut self, group: &'a Group) {
+        walk_group(self, group)
+    }
+
+    /// Visit a choose element.
+    fn visit_choose(&mut self, choose: &'a Choose) {
+        walk_choose(self, choose)
+    }
+
+    /// Visit an `if` or `else-if` branch of a choose element.
+    fn visit_choose_branch(&mut self, branch: &'a ChooseBranch) {
+        walk_choose_branch(self, branch)
+    }
+
+    /// Visit the `else` branch of a choose element.
+    fn visit_else_branch(&mut self, branch: &'a ElseBranch) {
+        walk_else_branch(self, branch)
+    }
+}
+
+/// Visit the macros, locales, citation, and bibliography of a style, in this
+/// order.
+pub fn walk_style<'a, V: Visit<'a> + ?Sized>(v: &mut V, style: &'a IndependentStyle) {
+    for macro_ in &style.macros {
+        v.visit_macro(macro_);
+    }
+    for locale in &style.locale {
+        v.visit_locale(locale);
+    }
+    v.visit_citation(&style.citation);
+    if let Some(bibliography) = &style.bibliography {
+        v.visit_bibliography(bibliography);
+    }
+}
+
+/// Visit the children of a macro.
+pub fn walk_macro<'a, V: Visit<'a> + ?Sized>(v: &mut V, macro_: &'a CslMacro) {
+    walk_children(v, &macro_.children);
+}
+
+/// Visit the date formats of a locale.
+pub fn walk_locale<'a, V: Visit<'a> + ?Sized>(v: &mut V, locale: &'a Locale) {
+    for date in &locale.date {
+        v.visit_date(date);
+    }
+}
+
+/// Visit the sort and layout of a citation.
+pub fn walk_citation<'a, V: Visit<'a> + ?Sized>(v: &mut V, citation: &'a Citation) {
+    if let Some(sort) = &citation.sort {
+        v.visit_sort(sort);
+    }
+    v.visit_layout(&citation.layout);
+}
+
+/// Visit the sort and layout of a bibliography.
+pub fn walk_bibliography<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    bibliography: &'a Bibliography,
+) {
+    if let Some(sort) = &bibliography.sort {
+        v.visit_sort(sort);
+    }
+    v.visit_layout(&bibliography.layout);
+}
+
+/// Visit the keys of a sort element.
+pub fn walk_sort<'a, V: Visit<'a> + ?Sized>(v: &mut V, sort: &'a Sort) {
+    for key in &sort.keys {
+        v.visit_sort_key(key);
+    }
+}
+
+/// Visit the elements of a layout.
+pub fn walk_layout<'a, V: Visit<'a> + ?Sized>(v: &mut V, layout: &'a Layout) {
+    walk_children(v, &layout.elements);
+}
+
+/// Visit the layout or element wrapped by a rendering element.
+pub fn walk_rendering_element<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    element: &'a RenderingElement,
+) {
+    match element {
+        RenderingElement::Layout(layout) => v.visit_layout(layout),
+        RenderingElement::Other(element) => v.visit_element(element),
+    }
+}
+
+/// Dispatch to the method for the kind of element.
+pub fn walk_element<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    element: &'a LayoutRenderingElement,
+) {
+    match element {
+        LayoutRenderingElement::Text(text) => v.visit_text(text),
+        LayoutRenderingElement::Date(date) => v.visit_date(date),
+        LayoutRenderingElement::Number(number) => v.visit_number(number),
+        LayoutRenderingElement::Names(names) => v.visit_names(names),
+        LayoutRenderingElement::Label(label) => v.visit_label(label),
+        LayoutRenderingElement::Group(group) => v.visit_group(group),
+        LayoutRenderingElement::Choose(choose) => v.visit_choose(choose),
+    }
+}
+
+/// Visit the date parts of a date.
+pub fn walk_date<'a, V: Visit<'a> + ?Sized>(v: &mut V, date: &'a Date) {
+    for part in &date.date_part {
+        v.visit_date_part(part);
+    }
+}
+
+/// Visit the children of a names element.
+pub fn walk_names<'a, V: Visit<'a> + ?Sized>(v: &mut V, names: &'a Names) {
+    for child in &names.children {
+        match child {
+            NamesChild::Name(name) => v.visit_name(name),
+            NamesChild::EtAl(et_al) => v.visit_et_al(et_al),
+            NamesChild::Label(label) => v.visit_names_label(label),
+            NamesChild::Substitute(substitute) => v.visit_substitute(substitute),
+        }
+    }
+}
+
+/// Visit the children of a substitute element.
+pub fn walk_substitute<'a, V: Visit<'a> + ?Sized>(v: &mut V, substitute: &'a Substitute) {
+    walk_children(v, &substitute.children);
+}
+
+/// Visit the children of a group.
+pub fn walk_group<'a, V: Visit<'a> + ?Sized>(v: &mut V, group: &'a Group) {
+    walk_children(v, &group.children);
+}
+
+/// Visit the branches of a choose element.
+pub fn walk_choose<'a, V: Visit<'a> + ?Sized>(v: &mut V, choose: &'a Choose) {
+    for branch in choose.branches() {
+        v.visit_choose_branch(branch);
+    }
+    if let Some(otherwise) = &choose.otherwise {
+        v.visit_else_branch(otherwise);
+    }
+}
+
+/// Visit the children of an `if` or `else-if` branch.
+pub fn walk_choose_branch<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    branch: &'a ChooseBranch,
+) {
+    walk_children(v, &branch.children);
+}
+
+/// Visit the children of an `else` branch.
+pub fn walk_else_branch<'a, V: Visit<'a> + ?Sized>(v: &mut V, branch: &'a ElseBranch) {
+    walk_children(v, &branch.children);
+}
+
+fn walk_children<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    children: &'a [LayoutRenderingElement],
+) {
+    for child in children {
+        v.visit_element(child);
+    }
+}
+
+/// Rewrite the nodes of a style in place.
+#[allow(unused_variables)]
+pub trait VisitMut {
+    /// Visit a style and its macros, locales, citation, and bibliography.
+    fn visit_style_mut(&mut self, style: &mut IndependentStyle) {
+        walk_style_mut(self, style)
+    }
+
+    /// Visit a macro definition.
+    fn visit_macro_mut(&mut self, macro_: &mut CslMacro) {
+        walk_macro_mut(self, macro_)
+    }
+
+    /// Visit the localization overrides of a style.
+    fn visit_locale_mut(&mut self, locale: &mut Locale) {
+        walk_locale_mut(self, locale)
+    }
+
+    /// Visit the citation element.
+    fn visit_citation_mut(&mut self, citation: &mut Citation) {
+        walk_citation_mut(self, citation)
+    }
+
+    /// Visit the bibliography element.
+    fn visit_bibliography_mut(&mut self, bibliography: &mut Bibliography) {
+        walk_bibliography_mut(self, bibliography)
+    }
+
+    /// Visit the sort element of a citation or bibliography.
+    fn visit_sort_mut(&mut self, sort: &mut Sort) {
+        walk_sort_mut(self, sort)
+    }
+
+    /// Visit a sort key.
+    fn visit_sort_key_mut(&mut self, key: &mut SortKey) {}
+
+    /// Visit the layout of a citation or bibliography.
+    fn visit_layout_mut(&mut self, layout: &mut Layout) {
+        walk_layout_mut(self, layout)
+    }
+
+    /// Visit a layout or another rendering element.
+    fn visit_rendering_element_mut(&mut self, element: &mut RenderingElement) {
+        walk_rendering_element_mut(self, element)
+    }
+
+    /// Visit any rendering element within a layout. Override this to replace
+    /// elements with elements of a different kind.
+    fn visit_element_mut(&mut self, element: &mut LayoutRenderingElement) {
+        walk_element_mut(self, element)
+    }
+
+    /// Visit a text element.
+    fn visit_text_mut(&mut self, text: &mut Text) {}
+
+    /// Visit a date element.
+    fn visit_date_mut(&mut self, date: &mut Date) {
+        walk_date_mut(self, date)
+    }
+
+    /// Visit a date part within a date element.
+    fn visit_date_part_mut(&mut self, part: &mut DatePart) {}
+
+    /// Visit a number element.
+    fn visit_number_mut(&mut self, number: &mut Number) {}
+
+    /// Visit a names element.
+    fn visit_names_mut(&mut self, names: &mut Names) {
+        walk_names_mut(self, names)
+    }
+
+    /// Visit a name element within a names element.
+    fn visit_name_mut(&mut self, name: &mut Name) {}
+
+    /// Visit an et-al element within a names element.
+    fn visit_et_al_mut(&mut self, et_al: &mut EtAl) {}
+
+    /// Visit a label element within a names element.
+    fn visit_names_label_mut(&mut self, label: &mut VariablelessLabel) {}
+
+    /// Visit the substitute element of a names element.
+    fn visit_substitute_mut(&mut self, substitute: &mut Substitute) {
+        walk_substitute_mut(self, substitute)
+    }
+
+    /// Visit a label element.
+    fn visit_label_mut(&mut self, label: &mut Label) {}
+
+    /// Visit a group element.
+    fn visit_group_mut(&mut self, group: &mut Group) {
+        walk_group_mut(self, group)
+    }
+
+    /// Visit a choose element.
+    fn visit_choose_mut(&mut self, choose: &mut Choose) {
+        walk_choose_mut(self, choose)
+    }
+
+    /// Visit an `if` or `else-if` branch of a choose element.
+    fn visit_choose_branch_mut(&mut self, branch: &mut ChooseBranch) {
+        walk_choose_branch_mut(self, branch)
+    }
+
+    /// Visit the `else` branch of a choose element.
+    fn visit_else_branch_mut(&mut self, branch: &mut ElseBranch) {
+        walk_else_branch_mut(self, branch)
+    }
+}
+
+/// Visit the macros, locales, citation, and bibliography of a style, in this
+/// order.
+pub fn walk_style_mut<V: VisitMut + ?Sized>(v: &mut V, style: &mut IndependentStyle) {
+    for macro_ in &mut style.macros {
+        v.visit_macro_mut(macro_);
+    }
+    for locale in &mut style.locale {
+        v.visit_locale_mut(locale);
+    }
+    v.visit_citation_mut(&mut style.citation);
+    if let Some(bibliography) = &mut style.bibliography {
+        v.visit_bibliography_mut(bibliography);
+    }
+}
+
+/// Visit the children of a macro.
+pub fn walk_macro_mut<V: VisitMut + ?Sized>(v: &mut V, macro_: &mut CslMacro) {
+    walk_children_mut(v, &mut macro_.children);
+}
+
+/// Visit the date formats of a locale.
+pub fn walk_locale_mut<V: VisitMut + ?Sized>(v: &mut V, locale: &mut Locale) {
+    for date in &mut locale.date {
+        v.visit_date_mut(date);
+    }
+}
+
+/// Visit the sort and layout of a citation.
+pub fn walk_citation_mut<V: VisitMut + ?Sized>(v: &mut V, citation: &mut Citation) {
+    if let Some(sort) = &mut citation.sort {
+        v.visit_sort_mut(sort);
+    }
+    v.visit_layout_mut(&mut citation.layout);
+}
+
+/// Visit the sort and layout of a bibliography.
+pub fn walk_bibliography_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    bibliography: &mut Bibliography,
+) {
+    if let Some(sort) = &mut bibliography.sort {
+        v.visit_sort_mut(sort);
+    }
+    v.visit_layout_mut(&mut bibliography.layout);
+}
+
+/// Visit the keys of a sort element.
+pub fn walk_sort_mut<V: VisitMut + ?Sized>(v: &mut V, sort: &mut Sort) {
+    for key in &mut sort.keys {
+        v.visit_sort_key_mut(key);
+    }
+}
+
+/// Visit the elements of a layout.
+pub fn walk_layout_mut<V: VisitMut + ?Sized>(v: &mut V, layout: &mut Layout) {
+    walk_children_mut(v, &mut layout.elements);
+}
+
+/// Visit the layout or element wrapped by a rendering element.
+pub fn walk_rendering_element_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    element: &mut RenderingElement,
+) {
+    match element {
+        RenderingElement::Layout(layout) => v.visit_layout_mut(layout),
+        RenderingElement::Other(element) => v.visit_element_mut(element),
+    }
+}
+
+/// Dispatch to the method for the kind of element.
+pub fn walk_element_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    element: &mut LayoutRenderingElement,
+) {
+    match element {
+        LayoutRenderingElement::Text(text) => v.visit_text_mut(text),
+        LayoutRenderingElement::Date(date) => v.visit_date_mut(date),
+        LayoutRenderingElement::Number(number) => v.visit_number_mut(number),
+        LayoutRenderingElement::Names(names) => v.visit_names_mut(names),
+        LayoutRenderingElement::Label(label) => v.visit_label_mut(label),
+        LayoutRenderingElement::Group(group) => v.visit_group_mut(group),
+        LayoutRenderingElement::Choose(choose) => v.visit_choose_mut(choose),
+    }
+}
+
+/// Visit the date parts of a date.
+pub fn walk_date_mut<V: VisitMut + ?Sized>(v: &mut V, date: &mut Date) {
+    for part in &mut date.date_part {
+        v.visit_date_part_mut(part);
+    }
+}
+
+/// Visit the children of a names element.
+pub fn walk_names_mut<V: VisitMut + ?Sized>(v: &mut V, names: &mut Names) {
+    for child in &mut names.children {
+        match child {
+            NamesChild::Name(name) => v.visit_name_mut(name),
+            NamesChild::EtAl(et_al) => v.visit_et_al_mut(et_al),
+            NamesChild::Label(label) => v.visit_names_label_mut(label),
+            NamesChild::Substitute(substitute) => v.visit_substitute_mut(substitute),
+        }
+    }
+}
+
+/// Visit the children of a substitute element.
+pub fn walk_substitute_mut<V: VisitMut + ?Sized>(v: &mut V, substitute: &mut Substitute) {
+    walk_children_mut(v, &mut substitute.children);
+}
+
+/// Visit the children of a group.
+pub fn walk_group_mut<V: VisitMut + ?Sized>(v: &mut V, group: &mut Group) {
+    walk_children_mut(v, &mut group.children);
+}
+
+/// Visit the branches of a choose element.
+pub fn walk_choose_mut<V: VisitMut + ?Sized>(v: &mut V, choose: &mut Choose) {
+    v.visit_choose_branch_mut(&mut choose.if_);
+    for branch in &mut choose.else_if {
+        v.visit_choose_branch_mut(branch);
+    }
+    if let Some(otherwise) = &mut choose.otherwise {
+        v.visit_else_branch_mut(otherwise);
+    }
+}
+
+/// Visit the children of an `if` or `else-if` branch.
+pub fn walk_choose_branch_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    branch: &mut ChooseBranch,
+) {
+    walk_children_mut(v, &mut branch.children);
+}
+
+/// Visit the children of an `else` branch.
+pub fn walk_else_branch_mut<V: VisitMut + ?Sized>(v: &mut V, branch: &mut ElseBranch) {
+    walk_children_mut(v, &mut branch.children);
+}
+
+fn walk_children_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    children: &mut [LayoutRenderingElement],
+) {
+    for child in children {
+        v.visit_element_mut(child);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TextTarget;
+    use crate::taxonomy::{StandardVariable, Variable};
+
+    const TITLE: Variable = Variable::Standard(StandardVariable::Title);
+
+    /// Collects the variables of text elements.
+    struct TextVariables<'a>(Vec<&'a Variable>);
+
+    impl<'a> Visit<'a> for TextVariables<'a> {
+        fn visit_text(&mut self, text: &'a Text) {
+            if let TextTarget::Variable { var, .. } = &text.target {
+                self.0.push(var);
+            }
+        }
+    }
+
+    /// Replaces the title variable with the short title.
+    struct ShortenTitles;
+
+    impl VisitMut for ShortenTitles {
+        fn visit_text_mut(&mut self, text: &mut Text) {
+            if let TextTarget::Variable { var, .. } = &mut text.target
+                && *var == TITLE
+            {
+                *var = Variable::Standard(StandardVariable::TitleShort);
+            }
+        }
+    }
+
+    #[test]
+    fn visit_style() {
+        let mut style = IndependentStyle::from_xml(include_str!(
+            "../tests/independent/chicago-author-date.csl"
+        ))
+        .unwrap();
+
+        let mut visitor = TextVariables(Vec::new());
+        visitor.visit_style(&style);
+        let titles = visitor.0.iter().filter(|&&&v| v == TITLE).count();
+        assert!(titles > 0);
+
+        ShortenTitles.visit_style_mut(&mut style);
+        let mut visitor = TextVariables(Vec::new());
+        visitor.visit_style(&style);
+        assert!(!visitor.0.contains(&&TITLE));
+    }
+}